@@ -0,0 +1,21 @@
+/// Resolves credentials from the standard AWS provider chain (environment
+/// vars, shared profile/config file, SSO, web-identity/STS for IRSA, then
+/// EC2/ECS IMDS) so a service authenticates the same way on a laptop and
+/// inside a cluster. Falls back to the MinIO dev defaults only if none of
+/// those sources resolve.
+pub async fn build_credentials_chain() -> aws_config::meta::credentials::CredentialsProviderChain {
+    let default_chain = aws_config::default_provider::credentials::DefaultCredentialsChain::builder()
+        .build()
+        .await;
+
+    let minio_fallback = aws_sdk_s3::config::Credentials::new(
+        std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_else(|_| "minio".to_string()),
+        std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_else(|_| "minio123".to_string()),
+        None,
+        None,
+        "minio-fallback",
+    );
+
+    aws_config::meta::credentials::CredentialsProviderChain::first_try("DefaultChain", default_chain)
+        .or_else("MinioFallback", minio_fallback)
+}
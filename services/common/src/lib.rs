@@ -0,0 +1,13 @@
+//! Shared infrastructure used by every service in this pipeline
+//! (extractor, normalizer, comparator, orchestrator): the `ObjectStore`
+//! abstraction over S3/Azure/GCS/local-disk backends, its retry-with-backoff
+//! wrapper, and the AWS credential provider chain. Each service previously
+//! carried its own copy of this code, which meant a fix (like adding retry
+//! support) had to be re-applied to every copy individually instead of
+//! landing once.
+
+pub mod credentials;
+pub mod object_store;
+
+pub use credentials::build_credentials_chain;
+pub use object_store::{backend_name, build_store, is_conflict, key_from_uri, ConflictError, ObjectStore};
@@ -0,0 +1,822 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use rand::Rng;
+use std::fmt::Debug;
+use std::future::Future;
+use std::time::Duration;
+
+/// Returned by `put_if_match` when `expected_etag` no longer matches the
+/// object's current state -- i.e. someone else wrote it first. Callers
+/// distinguish this from other failures with `is_conflict` and reload the
+/// object to retry their read-modify-write instead of giving up or, worse,
+/// blindly overwriting the concurrent write.
+#[derive(Debug)]
+pub struct ConflictError {
+    pub key: String,
+    pub expected: Option<String>,
+}
+
+impl std::fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "conditional write to {} conflicted: expected etag {:?}",
+            self.key, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+/// True if `err` (or one of its sources) is a `ConflictError` from a failed
+/// `put_if_match`.
+pub fn is_conflict(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ConflictError>().is_some()
+}
+
+/// Payloads larger than this go through the multipart upload path instead of
+/// a single `put_object`, so uploading a large chunk file doesn't buffer the
+/// whole object in one request or stall on a single oversized PUT.
+const MULTIPART_THRESHOLD_BYTES: usize = 5 * 1024 * 1024;
+const MULTIPART_PART_SIZE_BYTES: usize = 8 * 1024 * 1024;
+
+/// Tuning for `retry_with_backoff`, read once from env vars so operators can
+/// tune retry behavior per deployment without a rebuild.
+struct RetryConfig {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryConfig {
+    fn from_env() -> Self {
+        let max_attempts = std::env::var("S3_RETRY_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+        let base_delay_ms = std::env::var("S3_RETRY_BASE_DELAY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+        let max_delay_ms = std::env::var("S3_RETRY_MAX_DELAY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5_000);
+        Self {
+            max_attempts,
+            base_delay: Duration::from_millis(base_delay_ms),
+            max_delay: Duration::from_millis(max_delay_ms),
+        }
+    }
+}
+
+/// 5xx, throttling (429/503 `SlowDown`), and connection/timeout failures are
+/// worth retrying; 4xx responses like 404/403 and any other client error
+/// never succeed on retry, so they fail fast.
+fn is_retryable<E: Debug>(err: &SdkError<E>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(ctx) => {
+            let status = ctx.raw().status().as_u16();
+            status >= 500 || status == 429
+        }
+        _ => false,
+    }
+}
+
+/// Retries `op` with full-jitter exponential backoff (`base * 2^attempt`,
+/// capped at `max_delay`, then a random duration drawn from `[0, delay]`) up
+/// to `config.max_attempts`, so a brief store outage doesn't fail the call
+/// and a flood of workers retrying in lockstep doesn't hammer it either.
+async fn retry_with_backoff<T, E, F, Fut>(config: &RetryConfig, op: F) -> Result<T, SdkError<E>>
+where
+    E: Debug,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, SdkError<E>>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < config.max_attempts && is_retryable(&e) => {
+                let exp = config.base_delay.saturating_mul(1u32 << (attempt - 1).min(20));
+                let capped = exp.min(config.max_delay);
+                let jittered = capped.mul_f64(rand::thread_rng().gen_range(0.0..=1.0));
+                tracing::warn!(
+                    "S3 operation failed (attempt {}/{}): {:?}. Retrying in {:?}",
+                    attempt,
+                    config.max_attempts,
+                    e,
+                    jittered
+                );
+                tokio::time::sleep(jittered).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Backend-agnostic object storage. Lets a service run against MinIO, real
+/// AWS, Azure Blob, GCS, or a local filesystem dev disk without recompiling
+/// (selected at startup via `STORE_BACKEND`).
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// Fetches only the half-open `[start, end)` byte range of `key`, so
+    /// callers that only need a small slice of a large object (e.g. a PDF
+    /// trailer) don't have to download the whole thing.
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>>;
+
+    /// Returns the size of `key` in bytes, without downloading it.
+    async fn size(&self, key: &str) -> Result<u64>;
+
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()>;
+
+    /// Like `get`, but also returns the object's current ETag, for callers
+    /// that intend to feed it back into `put_if_match`.
+    async fn get_with_etag(&self, key: &str) -> Result<(Vec<u8>, String)>;
+
+    /// Writes `bytes` to `key` only if the object's current ETag equals
+    /// `expected_etag` (`None` meaning "only create it; the key must not
+    /// already exist"). Returns the new ETag on success, or a
+    /// `ConflictError` (check with `is_conflict`) if someone else wrote
+    /// `key` first -- callers should reload and retry their
+    /// read-modify-write rather than treat this as a hard failure.
+    async fn put_if_match(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        expected_etag: Option<&str>,
+    ) -> Result<String>;
+
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    fn list(&self, prefix: &str) -> BoxStream<'_, Result<String>>;
+}
+
+pub struct S3Store {
+    pub client: aws_sdk_s3::Client,
+    pub bucket: String,
+    retry_config: RetryConfig,
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let resp = retry_with_backoff(&self.retry_config, || {
+            self.client.get_object().bucket(&self.bucket).key(key).send()
+        })
+        .await
+        .context("failed to get object from S3")?;
+        let data = resp.body.collect().await?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        let range = format!("bytes={}-{}", start, end.saturating_sub(1));
+        let resp = retry_with_backoff(&self.retry_config, || {
+            self.client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .range(&range)
+                .send()
+        })
+        .await
+        .context("failed to get object range from S3")?;
+        let data = resp.body.collect().await?;
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn size(&self, key: &str) -> Result<u64> {
+        let resp = retry_with_backoff(&self.retry_config, || {
+            self.client.head_object().bucket(&self.bucket).key(key).send()
+        })
+        .await
+        .context("failed to head object in S3")?;
+        Ok(resp.content_length().unwrap_or(0) as u64)
+    }
+
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+        if bytes.len() <= MULTIPART_THRESHOLD_BYTES {
+            retry_with_backoff(&self.retry_config, || {
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .body(bytes.clone().into())
+                    .send()
+            })
+            .await
+            .context("failed to put object to S3")?;
+            return Ok(());
+        }
+
+        self.put_multipart(key, bytes).await
+    }
+
+    async fn get_with_etag(&self, key: &str) -> Result<(Vec<u8>, String)> {
+        let resp = retry_with_backoff(&self.retry_config, || {
+            self.client.get_object().bucket(&self.bucket).key(key).send()
+        })
+        .await
+        .context("failed to get object from S3")?;
+        let etag = resp
+            .e_tag()
+            .context("get_object response missing ETag")?
+            .to_string();
+        let data = resp.body.collect().await?;
+        Ok((data.into_bytes().to_vec(), etag))
+    }
+
+    /// S3 returns 412 Precondition Failed when `If-Match`/`If-None-Match`
+    /// doesn't hold; that's the only case this translates into
+    /// `ConflictError` rather than propagating the raw SDK error, since
+    /// it's the only outcome callers should retry their read-modify-write
+    /// on instead of treating as a hard failure.
+    async fn put_if_match(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        expected_etag: Option<&str>,
+    ) -> Result<String> {
+        let result = retry_with_backoff(&self.retry_config, || {
+            let mut req = self.client.put_object().bucket(&self.bucket).key(key).body(bytes.clone().into());
+            req = match expected_etag {
+                Some(etag) => req.if_match(etag),
+                None => req.if_none_match("*"),
+            };
+            req.send()
+        })
+        .await;
+
+        match result {
+            Ok(resp) => resp
+                .e_tag()
+                .map(|s| s.to_string())
+                .context("put_object response missing ETag"),
+            Err(SdkError::ServiceError(ctx)) if ctx.raw().status().as_u16() == 412 => {
+                Err(ConflictError {
+                    key: key.to_string(),
+                    expected: expected_etag.map(|s| s.to_string()),
+                }
+                .into())
+            }
+            Err(e) => Err(e).context("failed to conditionally put object to S3"),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        retry_with_backoff(&self.retry_config, || {
+            self.client.delete_object().bucket(&self.bucket).key(key).send()
+        })
+        .await
+        .context("failed to delete object from S3")?;
+        Ok(())
+    }
+
+    /// Follows S3's continuation-token pagination (responses are capped at
+    /// 1000 keys) so a document with many pages/chunks isn't silently
+    /// truncated.
+    fn list(&self, prefix: &str) -> BoxStream<'_, Result<String>> {
+        enum PageState {
+            Next(Option<String>),
+            Done,
+        }
+
+        let prefix = prefix.to_string();
+        stream::unfold(PageState::Next(None), move |state| {
+            let prefix = prefix.clone();
+            async move {
+                let continuation_token = match state {
+                    PageState::Next(token) => token,
+                    PageState::Done => return None,
+                };
+
+                let resp = retry_with_backoff(&self.retry_config, || {
+                    self.client
+                        .list_objects_v2()
+                        .bucket(&self.bucket)
+                        .prefix(&prefix)
+                        .set_continuation_token(continuation_token.clone())
+                        .send()
+                })
+                .await;
+
+                let resp = match resp {
+                    Ok(resp) => resp,
+                    Err(e) => {
+                        tracing::error!("S3 list_objects_v2 failed for prefix {}: {}", prefix, e);
+                        let err = anyhow::anyhow!("S3 list failed: {}", e);
+                        return Some((vec![Err(err)], PageState::Done));
+                    }
+                };
+
+                let keys: Vec<Result<String>> = resp
+                    .contents
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|obj| obj.key)
+                    .map(Ok)
+                    .collect();
+
+                let next_state = if resp.is_truncated.unwrap_or(false) {
+                    PageState::Next(resp.next_continuation_token)
+                } else {
+                    PageState::Done
+                };
+
+                Some((keys, next_state))
+            }
+        })
+        .flat_map(stream::iter)
+        .boxed()
+    }
+}
+
+impl S3Store {
+    /// Streams `bytes` to `key` as fixed-size parts, completing the upload
+    /// once every part's ETag has been collected. Aborts the multipart
+    /// session on any part failure so no orphaned upload is left on the
+    /// bucket.
+    async fn put_multipart(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+        let create = retry_with_backoff(&self.retry_config, || {
+            self.client.create_multipart_upload().bucket(&self.bucket).key(key).send()
+        })
+        .await
+        .context("failed to initiate multipart upload")?;
+        let upload_id = create
+            .upload_id()
+            .context("multipart upload response missing upload id")?
+            .to_string();
+
+        let upload_result = self.upload_parts(key, &upload_id, &bytes).await;
+
+        let parts = match upload_result {
+            Ok(parts) => parts,
+            Err(e) => {
+                let _ = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(&upload_id)
+                    .send()
+                    .await;
+                return Err(e);
+            }
+        };
+
+        retry_with_backoff(&self.retry_config, || {
+            self.client
+                .complete_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(&upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts.clone()))
+                        .build(),
+                )
+                .send()
+        })
+        .await
+        .context("failed to complete multipart upload")?;
+
+        Ok(())
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        bytes: &[u8],
+    ) -> Result<Vec<CompletedPart>> {
+        let mut parts = Vec::new();
+        for (idx, chunk) in bytes.chunks(MULTIPART_PART_SIZE_BYTES).enumerate() {
+            let part_number = (idx + 1) as i32;
+            let uploaded = retry_with_backoff(&self.retry_config, || {
+                self.client
+                    .upload_part()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(chunk.to_vec().into())
+                    .send()
+            })
+            .await
+            .with_context(|| format!("failed to upload part {}", part_number))?;
+            let e_tag = uploaded
+                .e_tag()
+                .context("upload_part response missing ETag")?
+                .to_string();
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .e_tag(e_tag)
+                    .build(),
+            );
+        }
+        Ok(parts)
+    }
+}
+
+pub struct AzureStore {
+    inner: object_store::azure::MicrosoftAzure,
+}
+
+pub struct GcsStore {
+    inner: object_store::gcp::GoogleCloudStorage,
+}
+
+pub struct FileStore {
+    inner: object_store::local::LocalFileSystem,
+}
+
+macro_rules! impl_generic_object_store {
+    ($ty:ty) => {
+        #[async_trait]
+        impl ObjectStore for $ty {
+            async fn get(&self, key: &str) -> Result<Vec<u8>> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                let result = self.inner.get(&path).await?;
+                Ok(result.bytes().await?.to_vec())
+            }
+
+            async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                let bytes = self.inner.get_range(&path, start as usize..end as usize).await?;
+                Ok(bytes.to_vec())
+            }
+
+            async fn size(&self, key: &str) -> Result<u64> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                let meta = self.inner.head(&path).await?;
+                Ok(meta.size as u64)
+            }
+
+            async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                self.inner.put(&path, bytes.into()).await?;
+                Ok(())
+            }
+
+            async fn get_with_etag(&self, key: &str) -> Result<(Vec<u8>, String)> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                let result = self.inner.get(&path).await?;
+                let etag = result
+                    .meta
+                    .e_tag
+                    .clone()
+                    .context("object store backend did not return an ETag")?;
+                Ok((result.bytes().await?.to_vec(), etag))
+            }
+
+            async fn put_if_match(
+                &self,
+                key: &str,
+                bytes: Vec<u8>,
+                expected_etag: Option<&str>,
+            ) -> Result<String> {
+                use object_store::{ObjectStore as _, PutMode, UpdateVersion};
+                let path = object_store::path::Path::from(key);
+                let mode = match expected_etag {
+                    Some(etag) => PutMode::Update(UpdateVersion {
+                        e_tag: Some(etag.to_string()),
+                        version: None,
+                    }),
+                    None => PutMode::Create,
+                };
+                let opts = object_store::PutOptions {
+                    mode,
+                    ..Default::default()
+                };
+                match self.inner.put_opts(&path, bytes.into(), opts).await {
+                    Ok(result) => result.e_tag.context("put_opts response missing ETag"),
+                    Err(object_store::Error::AlreadyExists { .. })
+                    | Err(object_store::Error::Precondition { .. }) => Err(ConflictError {
+                        key: key.to_string(),
+                        expected: expected_etag.map(|s| s.to_string()),
+                    }
+                    .into()),
+                    Err(e) => Err(e.into()),
+                }
+            }
+
+            async fn delete(&self, key: &str) -> Result<()> {
+                use object_store::ObjectStore as _;
+                let path = object_store::path::Path::from(key);
+                self.inner.delete(&path).await?;
+                Ok(())
+            }
+
+            fn list(&self, prefix: &str) -> BoxStream<'_, Result<String>> {
+                use object_store::ObjectStore as _;
+                let prefix = object_store::path::Path::from(prefix);
+                self.inner
+                    .list(Some(&prefix))
+                    .map(|res| Ok(res?.location.to_string()))
+                    .boxed()
+            }
+        }
+    };
+}
+
+impl_generic_object_store!(AzureStore);
+impl_generic_object_store!(GcsStore);
+impl_generic_object_store!(FileStore);
+
+/// Strips the `s3://bucket/` (or bare `bucket/`) prefix from a stored URI.
+/// Unlike `str::trim_start_matches`, which strips a prefix repeatedly, this
+/// strips the scheme and then exactly one leading `{bucket_name}/` segment
+/// -- so a key that happens to start with a path segment equal to
+/// `bucket_name` (e.g. bucket `chunks` storing keys under `chunks/...`)
+/// isn't stripped a second time.
+pub fn key_from_uri(uri: &str, bucket_name: &str) -> String {
+    let without_scheme = uri.strip_prefix("s3://").unwrap_or(uri);
+    without_scheme
+        .strip_prefix(&format!("{}/", bucket_name))
+        .unwrap_or(without_scheme)
+        .to_string()
+}
+
+#[cfg(test)]
+mod key_from_uri_tests {
+    use super::key_from_uri;
+
+    #[test]
+    fn strips_scheme_and_bucket() {
+        assert_eq!(
+            key_from_uri("s3://my-bucket/doc-1/page.json", "my-bucket"),
+            "doc-1/page.json"
+        );
+    }
+
+    #[test]
+    fn strips_bucket_without_scheme() {
+        assert_eq!(key_from_uri("my-bucket/doc-1/page.json", "my-bucket"), "doc-1/page.json");
+    }
+
+    /// A bucket literally named after one of this codebase's own key
+    /// prefixes (`chunks`, `raw`, `segments`, `results`) is exactly
+    /// the case that broke `trim_start_matches`, which strips its pattern
+    /// repeatedly: `"chunks/chunks/doc-1/page.json"` would lose both
+    /// `chunks/` segments instead of just the bucket's.
+    #[test]
+    fn bucket_name_colliding_with_key_prefix_is_only_stripped_once() {
+        assert_eq!(
+            key_from_uri("s3://chunks/chunks/doc-1/page.json", "chunks"),
+            "chunks/doc-1/page.json"
+        );
+    }
+
+    #[test]
+    fn no_match_returns_uri_unchanged() {
+        assert_eq!(
+            key_from_uri("other-bucket/doc-1/page.json", "my-bucket"),
+            "other-bucket/doc-1/page.json"
+        );
+    }
+}
+
+/// Name of the backend `STORE_BACKEND` selects (`s3` by default). Exposed
+/// so callers can gate backend-specific functionality -- notably SigV4
+/// presigning, which only the `s3` backend supports -- without
+/// re-deriving which backend `build_store` picked.
+pub fn backend_name() -> String {
+    std::env::var("STORE_BACKEND").unwrap_or_else(|_| "s3".to_string())
+}
+
+/// Builds the configured `ObjectStore` backend from environment variables.
+/// `STORE_BACKEND` selects the implementation (`s3` is the default so
+/// existing MinIO/AWS deployments are unaffected); backend-specific
+/// settings are read from their usual env vars.
+pub async fn build_store(
+    s3_client: aws_sdk_s3::Client,
+    bucket_name: String,
+) -> Result<Box<dyn ObjectStore>> {
+    let backend = backend_name();
+
+    match backend.as_str() {
+        "s3" => Ok(Box::new(S3Store {
+            client: s3_client,
+            bucket: bucket_name,
+            retry_config: RetryConfig::from_env(),
+        })),
+        "azure" => {
+            let inner = object_store::azure::MicrosoftAzureBuilder::from_env()
+                .with_container_name(bucket_name)
+                .build()
+                .context("failed to build Azure Blob store")?;
+            Ok(Box::new(AzureStore { inner }))
+        }
+        "gcs" => {
+            let inner = object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                .with_bucket_name(bucket_name)
+                .build()
+                .context("failed to build GCS store")?;
+            Ok(Box::new(GcsStore { inner }))
+        }
+        "file" => {
+            let root = std::env::var("STORE_FILE_ROOT").unwrap_or_else(|_| "./data".to_string());
+            let inner = object_store::local::LocalFileSystem::new_with_prefix(&root)
+                .context("failed to open local filesystem store")?;
+            Ok(Box::new(FileStore { inner }))
+        }
+        other => anyhow::bail!("unknown STORE_BACKEND: {}", other),
+    }
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use super::*;
+    use aws_smithy_types::body::SdkBody;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Debug)]
+    struct DummyError;
+
+    fn service_error(status: u16) -> SdkError<DummyError> {
+        let raw = http::Response::builder()
+            .status(status)
+            .body(SdkBody::empty())
+            .unwrap();
+        SdkError::service_error(DummyError, raw.into())
+    }
+
+    #[test]
+    fn is_retryable_for_5xx_and_throttling() {
+        assert!(is_retryable(&service_error(500)));
+        assert!(is_retryable(&service_error(503)));
+        assert!(is_retryable(&service_error(429)));
+    }
+
+    #[test]
+    fn is_retryable_false_for_client_errors() {
+        assert!(!is_retryable(&service_error(404)));
+        assert!(!is_retryable(&service_error(403)));
+        assert!(!is_retryable(&service_error(400)));
+    }
+
+    #[test]
+    fn is_retryable_true_for_timeouts() {
+        let err: SdkError<DummyError> = SdkError::timeout_error("request timed out");
+        assert!(is_retryable(&err));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_with_backoff_retries_up_to_max_attempts_then_fails() {
+        let config = RetryConfig {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), SdkError<DummyError>> = retry_with_backoff(&config, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async { Err(service_error(503)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_with_backoff_does_not_retry_non_retryable_errors() {
+        let config = RetryConfig {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), SdkError<DummyError>> = retry_with_backoff(&config, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async { Err(service_error(404)) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            attempts.load(Ordering::SeqCst),
+            1,
+            "a non-retryable error should fail on the first attempt"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_with_backoff_succeeds_once_a_retry_stops_erroring() {
+        let config = RetryConfig {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(100),
+        };
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<u32, SdkError<DummyError>> = retry_with_backoff(&config, || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < 2 {
+                    Err(service_error(500))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
+
+#[cfg(test)]
+mod list_tests {
+    use super::*;
+    use aws_sdk_s3::config::{Credentials, Region};
+    use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
+    use aws_smithy_types::body::SdkBody;
+    use futures_util::TryStreamExt;
+
+    fn list_objects_v2_xml(keys: &[&str], is_truncated: bool, next_token: Option<&str>) -> String {
+        let contents: String = keys
+            .iter()
+            .map(|k| format!("<Contents><Key>{}</Key></Contents>", k))
+            .collect();
+        let next_token_xml = next_token
+            .map(|t| format!("<NextContinuationToken>{}</NextContinuationToken>", t))
+            .unwrap_or_default();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+             {}<IsTruncated>{}</IsTruncated>{}</ListBucketResult>",
+            contents, is_truncated, next_token_xml
+        )
+    }
+
+    fn replay_event(body: String) -> ReplayEvent {
+        ReplayEvent::new(
+            http::Request::builder()
+                .uri("https://test-bucket.s3.amazonaws.com/")
+                .body(SdkBody::empty())
+                .unwrap(),
+            http::Response::builder()
+                .status(200)
+                .body(SdkBody::from(body))
+                .unwrap(),
+        )
+    }
+
+    /// Two pages, the first `IsTruncated` with a continuation token and the
+    /// second not -- `list` must follow the token into the second page and
+    /// then stop, yielding keys from both in order, instead of stopping
+    /// after the first page or looping forever.
+    #[tokio::test]
+    async fn list_follows_continuation_token_and_stops_when_not_truncated() {
+        let page1 = list_objects_v2_xml(
+            &["segments/doc-1/000000-000010.json.zst"],
+            true,
+            Some("continuation-token-1"),
+        );
+        let page2 = list_objects_v2_xml(&["segments/doc-1/000010-000020.json.zst"], false, None);
+
+        let http_client = StaticReplayClient::new(vec![replay_event(page1), replay_event(page2)]);
+
+        let config = aws_sdk_s3::Config::builder()
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .region(Region::new("us-east-1"))
+            .http_client(http_client)
+            .behavior_version_latest()
+            .build();
+
+        let store = S3Store {
+            client: aws_sdk_s3::Client::from_conf(config),
+            bucket: "test-bucket".to_string(),
+            retry_config: RetryConfig::from_env(),
+        };
+
+        let keys: Vec<String> = store.list("segments/doc-1/").try_collect().await.unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                "segments/doc-1/000000-000010.json.zst".to_string(),
+                "segments/doc-1/000010-000020.json.zst".to_string(),
+            ]
+        );
+    }
+}
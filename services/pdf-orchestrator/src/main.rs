@@ -4,9 +4,18 @@ use lapin::{
     options::*, types::FieldTable, BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 
+mod pdf_trailer;
+mod store;
+use common::build_credentials_chain;
+use store::ObjectStore;
+
+/// Default lifetime for a presigned GET URL when `PRESIGN_EXPIRY_SECS` isn't
+/// set.
+const DEFAULT_PRESIGN_EXPIRY: Duration = Duration::from_secs(900);
+
 #[derive(Debug, Deserialize)]
 struct IngestPdfMessage {
     doc_id: String,
@@ -20,13 +29,29 @@ struct PageReadyMessage {
     page_no: i32,
     s3_uri: String,
     sha256: String,
+    /// Total pages in the document, so downstream consumers that batch
+    /// pages per document (e.g. the extractor's segment buffer) can
+    /// recognize the last page and flush a trailing partial batch instead
+    /// of waiting for a threshold that may never be crossed.
+    total_pages: i32,
+    /// Time-limited presigned GET URL for `s3_uri`, set when
+    /// `PRESIGN_URLS=true` so stateless downstream consumers can fetch the
+    /// PDF over HTTPS without holding S3 credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presigned_url: Option<String>,
 }
 
 struct Orchestrator {
     _connection: Connection,
     channel: Channel,
+    store: Box<dyn ObjectStore>,
+    // Kept alongside `store` purely to sign presigned URLs; the generic
+    // `ObjectStore` trait doesn't (and shouldn't) expose S3-specific
+    // presigning to the other backends.
     s3_client: aws_sdk_s3::Client,
     bucket_name: String,
+    presign_urls: bool,
+    presign_expiry: Duration,
 }
 
 impl Orchestrator {
@@ -61,16 +86,7 @@ impl Orchestrator {
             .await?;
 
         // Configure S3 client
-        let access_key = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_else(|_| "minio".to_string());
-        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_else(|_| "minio123".to_string());
-
-        let credentials = aws_sdk_s3::config::Credentials::new(
-            access_key,
-            secret_key,
-            None,
-            None,
-            "static",
-        );
+        let credentials = build_credentials_chain().await;
 
         let s3_config = aws_sdk_s3::config::Builder::new()
             .endpoint_url(s3_endpoint)
@@ -80,45 +96,104 @@ impl Orchestrator {
             .build();
 
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let store_backend = store::backend_name();
+        let store = store::build_store(s3_client.clone(), bucket_name.clone()).await?;
+
+        let presign_urls = std::env::var("PRESIGN_URLS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        // `presign_get` only knows how to sign against `s3_client`, so it
+        // isn't meaningful for the Azure/GCS/local-filesystem backends the
+        // `ObjectStore` abstraction also supports: a URL signed against S3
+        // would point at an endpoint nothing was ever written to, and
+        // downstream consumers would fail silently against it. Fail fast at
+        // startup instead.
+        if presign_urls && store_backend != "s3" {
+            anyhow::bail!(
+                "PRESIGN_URLS=true requires STORE_BACKEND=s3, but it is set to {}",
+                store_backend
+            );
+        }
+        let presign_expiry = std::env::var("PRESIGN_EXPIRY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_PRESIGN_EXPIRY);
 
         Ok(Self {
             _connection: conn,
             channel,
+            store,
             s3_client,
             bucket_name,
+            presign_urls,
+            presign_expiry,
         })
     }
 
     async fn download_from_s3(&self, s3_uri: &str) -> Result<Vec<u8>> {
-        let key = s3_uri
-            .trim_start_matches("s3://")
-            .trim_start_matches(&format!("{}/", self.bucket_name));
+        let key = store::key_from_uri(s3_uri, &self.bucket_name);
+        self.store.get(&key).await
+    }
 
-        let resp = self
+    /// Generates a presigned GET URL for `s3_uri`, signed with SigV4 and
+    /// valid for `self.presign_expiry`.
+    async fn presign_get(&self, s3_uri: &str) -> Result<String> {
+        let key = store::key_from_uri(s3_uri, &self.bucket_name);
+        let presigned = self
             .s3_client
             .get_object()
             .bucket(&self.bucket_name)
-            .key(key)
-            .send()
+            .key(&key)
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(
+                self.presign_expiry,
+            )?)
             .await?;
-
-        let data = resp.body.collect().await?;
-        Ok(data.into_bytes().to_vec())
+        Ok(presigned.uri().to_string())
     }
 
     async fn process_pdf(&self, msg: IngestPdfMessage) -> Result<()> {
         let start = Instant::now();
         info!("Orchestrating PDF processing for doc {}", msg.doc_id);
 
-        // Download PDF
-        let pdf_bytes = self.download_from_s3(&msg.s3_uri).await?;
-
-        // Load PDF to get page count
-        let doc = lopdf::Document::load_mem(&pdf_bytes)?;
-        let page_count = doc.get_pages().len();
+        // Try to get the page count from just the trailer, xref table, and
+        // root Pages object via a few small range reads, so we don't have
+        // to buffer the whole (potentially huge) PDF just to count pages.
+        let key = store::key_from_uri(&msg.s3_uri, &self.bucket_name);
+        let page_count = match pdf_trailer::streaming_page_count(self.store.as_ref(), &key).await
+        {
+            Ok(Some(count)) => count,
+            Ok(None) => {
+                info!(
+                    "Doc {} isn't range-parseable (linearized, xref stream, or incremental update); falling back to full download",
+                    msg.doc_id
+                );
+                let pdf_bytes = self.download_from_s3(&msg.s3_uri).await?;
+                lopdf::Document::load_mem(&pdf_bytes)?.get_pages().len()
+            }
+            Err(e) => {
+                error!("Streaming page count failed for doc {}: {}; falling back to full download", msg.doc_id, e);
+                let pdf_bytes = self.download_from_s3(&msg.s3_uri).await?;
+                lopdf::Document::load_mem(&pdf_bytes)?.get_pages().len()
+            }
+        };
 
         info!("PDF has {} pages, fanning out...", page_count);
 
+        // The same PDF object backs every page, so presign it once up front
+        // rather than per page.
+        let presigned_url = if self.presign_urls {
+            match self.presign_get(&msg.s3_uri).await {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    error!("Failed to presign {}: {}", msg.s3_uri, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         // Fan out page processing messages
         for page_no in 0..page_count {
             let page_msg = PageReadyMessage {
@@ -126,6 +201,8 @@ impl Orchestrator {
                 page_no: page_no as i32,
                 s3_uri: msg.s3_uri.clone(),
                 sha256: msg.sha256.clone(),
+                total_pages: page_count as i32,
+                presigned_url: presigned_url.clone(),
             };
 
             let payload = serde_json::to_vec(&page_msg)?;
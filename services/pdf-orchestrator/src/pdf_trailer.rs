@@ -0,0 +1,375 @@
+use crate::store::ObjectStore;
+use anyhow::Result;
+
+/// How far from the end of the file to scan for `startxref`/the trailer
+/// dictionary. Large enough for the trailer plus a classic xref table's
+/// subsection header to fit in one range read.
+const TAIL_SCAN_BYTES: u64 = 2_048;
+
+/// Upper bound on how much of the classic xref table we'll read in one
+/// range request. A document with more objects than this fits in falls
+/// back to a full download rather than growing the read unbounded.
+const MAX_XREF_SCAN_BYTES: u64 = 256 * 1024;
+
+/// Tries to obtain a PDF's page count from just its trailer, xref table,
+/// and root Pages object, fetched via a handful of small HTTP range reads
+/// instead of downloading the whole document.
+///
+/// Returns `Ok(None)` whenever the document doesn't use the classic
+/// (non-compressed) xref table format this parser understands — linearized
+/// PDFs, PDF 1.5+ cross-reference streams, and incrementally-updated files
+/// with a `/Prev` chain all fall outside its scope. Callers should fall back
+/// to a full download and a real parser (`lopdf`) in that case.
+pub async fn streaming_page_count(store: &dyn ObjectStore, key: &str) -> Result<Option<usize>> {
+    let size = store.size(key).await?;
+    let tail_len = TAIL_SCAN_BYTES.min(size);
+    let tail = store.get_range(key, size - tail_len, size).await?;
+
+    let Some(xref_offset) = find_startxref_offset(&tail) else {
+        return Ok(None);
+    };
+    if xref_offset >= size {
+        return Ok(None);
+    }
+
+    let scan_len = MAX_XREF_SCAN_BYTES.min(size - xref_offset);
+    let xref_section = store.get_range(key, xref_offset, xref_offset + scan_len).await?;
+
+    let Some(xref_table) = parse_classic_xref_table(&xref_section) else {
+        return Ok(None);
+    };
+    let Some(trailer) = parse_trailer_dict(&xref_section) else {
+        return Ok(None);
+    };
+    if trailer.prev.is_some() {
+        // An incremental update: following the /Prev chain to get a
+        // complete object table is more than this fast path is worth.
+        return Ok(None);
+    }
+
+    let Some(&root_offset) = xref_table.get(&trailer.root) else {
+        return Ok(None);
+    };
+    let root_obj = store
+        .get_range(key, root_offset, (root_offset + 1024).min(size))
+        .await?;
+    // The xref table is a flat offset list with no internal checksum; a
+    // corrupted or non-strictly-compliant entry can point at the wrong
+    // byte and still "successfully" pattern-match garbage. Verify the
+    // object header at the offset actually claims to be `trailer.root`
+    // before trusting anything found inside it.
+    if !validate_object_header(&root_obj, trailer.root) {
+        return Ok(None);
+    }
+    let Some(pages_ref) = find_indirect_ref(&root_obj, b"/Pages") else {
+        return Ok(None);
+    };
+    let Some(&pages_offset) = xref_table.get(&pages_ref) else {
+        return Ok(None);
+    };
+
+    let pages_obj = store
+        .get_range(key, pages_offset, (pages_offset + 1024).min(size))
+        .await?;
+    if !validate_object_header(&pages_obj, pages_ref) {
+        return Ok(None);
+    }
+    let Some(count) = find_integer(&pages_obj, b"/Count") else {
+        return Ok(None);
+    };
+
+    Ok(Some(count))
+}
+
+struct Trailer {
+    root: u32,
+    prev: Option<u64>,
+}
+
+/// Finds the last `startxref\n<offset>` pair in `tail` (the final one wins,
+/// matching how readers resolve the most recent trailer).
+fn find_startxref_offset(tail: &[u8]) -> Option<u64> {
+    let pos = find_last(tail, b"startxref")?;
+    let rest = &tail[pos + b"startxref".len()..];
+    let digits: String = rest
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit())
+        .map(|&b| b as char)
+        .collect();
+    digits.parse().ok()
+}
+
+/// Parses a classic (non-compressed) xref table into a map of object number
+/// to byte offset. Returns `None` if `section` doesn't start with an `xref`
+/// keyword, signalling a cross-reference stream instead.
+fn parse_classic_xref_table(section: &[u8]) -> Option<std::collections::HashMap<u32, u64>> {
+    let section = skip_whitespace(section);
+    let section = section.strip_prefix(b"xref")?;
+    let mut table = std::collections::HashMap::new();
+    let mut rest = skip_whitespace(section);
+
+    loop {
+        if rest.starts_with(b"trailer") || rest.is_empty() {
+            break;
+        }
+
+        let (start_obj, after_start) = take_uint(rest)?;
+        let after_start = skip_whitespace(after_start);
+        let (count, after_count) = take_uint(after_start)?;
+        let mut line_start = skip_whitespace(after_count);
+
+        for i in 0..count {
+            if line_start.len() < 18 {
+                return None;
+            }
+            let offset: u64 = std::str::from_utf8(&line_start[0..10]).ok()?.parse().ok()?;
+            let kind = line_start.get(17).copied();
+            if kind == Some(b'n') {
+                table.insert(start_obj as u32 + i as u32, offset);
+            }
+            line_start = skip_whitespace(&line_start[20.min(line_start.len())..]);
+        }
+
+        rest = line_start;
+    }
+
+    Some(table)
+}
+
+/// Parses the trailer dictionary's `/Root` and `/Prev` entries out of the
+/// text following the xref table.
+fn parse_trailer_dict(section: &[u8]) -> Option<Trailer> {
+    let pos = find_first(section, b"trailer")?;
+    let dict = &section[pos..];
+    let root_ref = find_indirect_ref(dict, b"/Root")?;
+    let prev = find_integer(dict, b"/Prev").map(|n| n as u64);
+    Some(Trailer { root: root_ref, prev })
+}
+
+/// Checks that `data` begins with `N G obj` for `expected_obj`, i.e. that
+/// whatever the xref table pointed us at is actually the object we asked
+/// for, not an offset into unrelated or corrupted bytes.
+fn validate_object_header(data: &[u8], expected_obj: u32) -> bool {
+    let data = skip_whitespace(data);
+    let Some((obj, rest)) = take_uint(data) else {
+        return false;
+    };
+    if obj as u32 != expected_obj {
+        return false;
+    }
+    let rest = skip_whitespace(rest);
+    let Some((_gen, rest)) = take_uint(rest) else {
+        return false;
+    };
+    skip_whitespace(rest).starts_with(b"obj")
+}
+
+/// Finds `/Name <obj> <gen> R` and returns `<obj>`.
+fn find_indirect_ref(data: &[u8], name: &[u8]) -> Option<u32> {
+    let pos = find_first(data, name)?;
+    let rest = skip_whitespace(&data[pos + name.len()..]);
+    let (obj, _) = take_uint(rest)?;
+    Some(obj as u32)
+}
+
+/// Finds `/Name <n>` and returns `<n>`.
+fn find_integer(data: &[u8], name: &[u8]) -> Option<usize> {
+    let pos = find_first(data, name)?;
+    let rest = skip_whitespace(&data[pos + name.len()..]);
+    let (n, _) = take_uint(rest)?;
+    Some(n)
+}
+
+fn take_uint(data: &[u8]) -> Option<(usize, &[u8])> {
+    let digits_len = data.iter().take_while(|b| b.is_ascii_digit()).count();
+    if digits_len == 0 {
+        return None;
+    }
+    let n: usize = std::str::from_utf8(&data[..digits_len]).ok()?.parse().ok()?;
+    Some((n, &data[digits_len..]))
+}
+
+fn skip_whitespace(data: &[u8]) -> &[u8] {
+    let start = data.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    &data[start..]
+}
+
+fn find_first(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Serves range reads out of an in-memory buffer, so the streaming
+    /// parser can be exercised against a synthetic classic-xref PDF without
+    /// a real object store.
+    struct MockStore {
+        data: Vec<u8>,
+    }
+
+    #[async_trait]
+    impl ObjectStore for MockStore {
+        async fn get(&self, _key: &str) -> Result<Vec<u8>> {
+            Ok(self.data.clone())
+        }
+
+        async fn get_range(&self, _key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+            let start = start as usize;
+            let end = (end as usize).min(self.data.len());
+            Ok(self.data[start..end].to_vec())
+        }
+
+        async fn size(&self, _key: &str) -> Result<u64> {
+            Ok(self.data.len() as u64)
+        }
+
+        async fn put(&self, _key: &str, _bytes: Vec<u8>) -> Result<()> {
+            unreachable!("not used by these tests")
+        }
+
+        async fn get_with_etag(&self, _key: &str) -> Result<(Vec<u8>, String)> {
+            unreachable!("not used by these tests")
+        }
+
+        async fn put_if_match(&self, _key: &str, _bytes: Vec<u8>, _expected_etag: Option<&str>) -> Result<String> {
+            unreachable!("not used by these tests")
+        }
+
+        async fn delete(&self, _key: &str) -> Result<()> {
+            unreachable!("not used by these tests")
+        }
+
+        fn list(&self, _prefix: &str) -> futures_util::stream::BoxStream<'_, Result<String>> {
+            unreachable!("not used by these tests")
+        }
+    }
+
+    /// One classic xref entry is always exactly 20 bytes: a 10-digit offset,
+    /// a space, a 5-digit generation, a space, the `n`/`f` keyword, and a
+    /// 2-byte EOL.
+    fn xref_entry(offset: u64, gen: u32, kind: char) -> String {
+        format!("{:010} {:05} {}\r\n", offset, gen, kind)
+    }
+
+    /// Builds a minimal classic (non-compressed) PDF with a one-subsection
+    /// xref table: object 1 is a `/Pages` node with `/Count count`, object 2
+    /// is the `/Catalog` root pointing at it.
+    fn build_synthetic_pdf(count: usize) -> (Vec<u8>, u64, u64) {
+        let header = b"%PDF-1.4\n".to_vec();
+
+        let obj1 = format!("1 0 obj\n<< /Type /Pages /Count {} >>\nendobj\n", count);
+        let obj2 = "2 0 obj\n<< /Type /Catalog /Pages 1 0 R >>\nendobj\n".to_string();
+
+        let offset1 = header.len() as u64;
+        let offset2 = offset1 + obj1.len() as u64;
+        let xref_offset = offset2 + obj2.len() as u64;
+
+        let xref = format!(
+            "xref\n0 3\n{}{}{}trailer\n<< /Root 2 0 R /Size 3 >>\nstartxref\n{}\n%%EOF",
+            xref_entry(0, 65535, 'f'),
+            xref_entry(offset1, 0, 'n'),
+            xref_entry(offset2, 0, 'n'),
+            xref_offset
+        );
+
+        let mut data = header;
+        data.extend_from_slice(obj1.as_bytes());
+        data.extend_from_slice(obj2.as_bytes());
+        data.extend_from_slice(xref.as_bytes());
+
+        (data, offset1, offset2)
+    }
+
+    #[tokio::test]
+    async fn streaming_page_count_reads_classic_xref() {
+        let (data, _, _) = build_synthetic_pdf(7);
+        let store = MockStore { data };
+
+        let count = streaming_page_count(&store, "doc.pdf").await.unwrap();
+        assert_eq!(count, Some(7));
+    }
+
+    #[tokio::test]
+    async fn streaming_page_count_rejects_xref_pointing_at_wrong_object() {
+        let (mut data, offset1, _) = build_synthetic_pdf(7);
+
+        // Corrupt the xref table's entry for object 1 so it points a few
+        // bytes past the start of its header (into the `obj` keyword). A
+        // `/Count` still follows that offset, so without the header sanity
+        // check this would return whatever count it finds there instead of
+        // falling back.
+        let bad_offset = offset1 + 5;
+        let xref_pos = find_first(&data, b"xref\n").unwrap();
+        let bad_entry_pos = xref_pos + "xref\n0 3\n".len() + xref_entry(0, 65535, 'f').len();
+        let replacement = xref_entry(bad_offset, 0, 'n');
+        data[bad_entry_pos..bad_entry_pos + replacement.len()]
+            .copy_from_slice(replacement.as_bytes());
+
+        let store = MockStore { data };
+        let count = streaming_page_count(&store, "doc.pdf").await.unwrap();
+        assert_eq!(count, None);
+    }
+
+    #[test]
+    fn validate_object_header_accepts_matching_object_number() {
+        assert!(validate_object_header(b"1 0 obj\n<< /Type /Pages >>", 1));
+    }
+
+    #[test]
+    fn validate_object_header_rejects_mismatched_object_number() {
+        assert!(!validate_object_header(b"2 0 obj\n<< /Type /Pages >>", 1));
+    }
+
+    #[test]
+    fn validate_object_header_rejects_non_header_bytes() {
+        assert!(!validate_object_header(b"<< /Type /Pages /Count 7 >>", 1));
+    }
+
+    #[test]
+    fn parse_classic_xref_table_handles_multiple_subsections() {
+        let section = format!(
+            "xref\n0 1\n{}3 2\n{}{}trailer\n<< /Root 3 0 R >>",
+            xref_entry(0, 65535, 'f'),
+            xref_entry(500, 0, 'n'),
+            xref_entry(600, 0, 'n'),
+        );
+
+        let table = parse_classic_xref_table(section.as_bytes()).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(&3), Some(&500));
+        assert_eq!(table.get(&4), Some(&600));
+        // Free entries (kind 'f') are never inserted.
+        assert_eq!(table.get(&0), None);
+    }
+
+    #[test]
+    fn parse_classic_xref_table_returns_none_for_xref_stream() {
+        // PDF 1.5+ cross-reference streams don't start with the `xref`
+        // keyword at all, so this is how we tell them apart.
+        let section = b"7 0 obj\n<< /Type /XRef /W [1 2 1] >>\nstream\n...";
+        assert!(parse_classic_xref_table(section).is_none());
+    }
+
+    #[test]
+    fn parse_trailer_dict_extracts_root_and_prev() {
+        let section = b"trailer\n<< /Root 5 0 R /Prev 9000 /Size 6 >>";
+        let trailer = parse_trailer_dict(section).unwrap();
+        assert_eq!(trailer.root, 5);
+        assert_eq!(trailer.prev, Some(9000));
+    }
+
+    #[test]
+    fn parse_trailer_dict_prev_absent_for_non_incremental_update() {
+        let section = b"trailer\n<< /Root 5 0 R /Size 6 >>";
+        let trailer = parse_trailer_dict(section).unwrap();
+        assert_eq!(trailer.prev, None);
+    }
+}
@@ -1,17 +1,22 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
     Router,
 };
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::info;
 use tower_http::cors::CorsLayer;
 
+mod store;
+use common::build_credentials_chain;
+use store::ObjectStore;
+
 #[derive(Debug, Deserialize, Clone)]
 struct Chunk {
     id: String,
@@ -21,6 +26,34 @@ struct Chunk {
     char_count: usize,
 }
 
+/// Mirrors the extractor's `Span`, trimmed to the fields needed to rebuild
+/// page text; the extra fields (x/y/w/h) in the stored records are simply
+/// ignored by serde.
+#[derive(Debug, Deserialize)]
+struct SegmentSpan {
+    text: String,
+    order: i32,
+}
+
+/// Mirrors the extractor's `PageRecord`.
+#[derive(Debug, Deserialize)]
+struct SegmentPageRecord {
+    page_no: i32,
+    spans: Vec<SegmentSpan>,
+}
+
+/// Mirrors the extractor's `SegmentRange`/`SegmentManifest`.
+#[derive(Debug, Deserialize)]
+struct SegmentRange {
+    start_page: i32,
+    key: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SegmentManifest {
+    segments: Vec<SegmentRange>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct CompareRequest {
     left_doc_id: String,
@@ -51,69 +84,140 @@ struct CompareResponse {
     processing_time_ms: u128,
     total_chunks_left: usize,
     total_chunks_right: usize,
+    /// doc_ids compared using the extractor's coarse segment fallback
+    /// (`load_segment_chunks`) instead of genuine normalizer chunks,
+    /// because `chunks/{doc_id}/` was empty when this comparison ran.
+    /// Non-empty means the percentages above were computed against
+    /// unnormalized, page-granularity text for at least one side --
+    /// callers should treat the result as provisional and retry once
+    /// normalization has caught up.
+    degraded_doc_ids: Vec<String>,
 }
 
 struct AppState {
+    store: Box<dyn ObjectStore>,
+    // Kept alongside `store` purely to sign presigned URLs; the generic
+    // `ObjectStore` trait doesn't (and shouldn't) expose S3-specific
+    // presigning to the other backends.
     s3_client: aws_sdk_s3::Client,
     bucket_name: String,
+    // `presign_upload`/`presign_result_download` only know how to sign
+    // against `s3_client`, so they refuse to run against any other
+    // `STORE_BACKEND` instead of silently handing out a URL that points at
+    // an S3 endpoint nobody is reading from or writing to.
+    store_backend: String,
 }
 
 impl AppState {
-    async fn download_from_s3(&self, key: &str) -> Result<Vec<u8>> {
-        let resp = self.s3_client
-            .get_object()
-            .bucket(&self.bucket_name)
-            .key(key)
-            .send()
-            .await?;
-
-        let data = resp.body.collect().await?;
-        Ok(data.into_bytes().to_vec())
-    }
-
-    async fn load_chunks(&self, doc_id: &str) -> Result<Vec<Chunk>> {
+    /// Returns `doc_id`'s chunks plus whether they came from the
+    /// coarse extractor-segment fallback rather than real normalizer
+    /// output, so callers can surface that degradation instead of
+    /// silently comparing mismatched-granularity text.
+    async fn load_chunks(&self, doc_id: &str) -> Result<(Vec<Chunk>, bool)> {
         let mut all_chunks = Vec::new();
 
-        // Try to list all chunk files for this document
+        // Paginate through every chunk file for this document; S3/MinIO caps
+        // list responses at 1000 keys, so large documents need multiple pages.
         let prefix = format!("chunks/{}/", doc_id);
-        info!("Listing S3 objects with prefix: {} in bucket: {}", prefix, self.bucket_name);
+        info!("Listing objects with prefix: {}", prefix);
 
-        let objects = self.s3_client
-            .list_objects_v2()
-            .bucket(&self.bucket_name)
-            .prefix(&prefix)
-            .send()
+        let keys: Vec<String> = self
+            .store
+            .list(&prefix)
+            .collect::<Vec<_>>()
             .await
-            .map_err(|e| {
-                tracing::error!("S3 list_objects_v2 failed for prefix {}: {}", prefix, e);
-                anyhow::anyhow!("S3 list failed: {}", e)
-            })?;
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        info!("Found {} chunk files for doc: {}", keys.len(), doc_id);
+        for key in keys {
+            info!("Downloading chunk file: {}", key);
+            let data = self.store.get(&key).await?;
+            let chunks: Vec<Chunk> = serde_json::from_slice(&data)
+                .map_err(|e| {
+                    tracing::error!("Failed to parse JSON from {}: {}", key, e);
+                    anyhow::anyhow!("JSON parse failed: {}", e)
+                })?;
+            info!("Loaded {} chunks from {}", chunks.len(), key);
+            all_chunks.extend(chunks);
+        }
 
-        if let Some(contents) = objects.contents {
-            info!("Found {} chunk files in S3 for doc: {}", contents.len(), doc_id);
-            for obj in contents {
-                if let Some(key) = obj.key {
-                    info!("Downloading chunk file: {}", key);
-                    let data = self.download_from_s3(&key).await?;
-                    let chunks: Vec<Chunk> = serde_json::from_slice(&data)
-                        .map_err(|e| {
-                            tracing::error!("Failed to parse JSON from {}: {}", key, e);
-                            anyhow::anyhow!("JSON parse failed: {}", e)
-                        })?;
-                    info!("Loaded {} chunks from {}", chunks.len(), key);
-                    all_chunks.extend(chunks);
-                }
-            }
-        } else {
-            tracing::warn!("No chunk files found in S3 for doc: {}", doc_id);
+        let mut degraded = false;
+        if all_chunks.is_empty() {
+            info!(
+                "No chunk files under {} for {}; falling back to extractor segments",
+                prefix, doc_id
+            );
+            all_chunks = self.load_segment_chunks(doc_id).await?;
+            degraded = true;
         }
 
         all_chunks.sort_by_key(|c| (c.page_no, c.order));
         info!("Total chunks loaded for {}: {}", doc_id, all_chunks.len());
-        Ok(all_chunks)
+        Ok((all_chunks, degraded))
     }
 
-    fn compare_chunks(&self, left: &Chunk, right: &Chunk) -> ChunkMatch {
+    /// Reads the extractor's zstd-compressed segment batches for `doc_id` (one
+    /// object per half-open page range plus a manifest listing them) and
+    /// decompresses/concatenates them in range order, converting each
+    /// buffered page's spans into one `Chunk` so it can be compared exactly
+    /// like a normalizer-produced chunk. This is the reader side of the
+    /// extractor's segment batching and is used whenever normalized chunks
+    /// aren't available yet (e.g. a document that's only been extracted).
+    async fn load_segment_chunks(&self, doc_id: &str) -> Result<Vec<Chunk>> {
+        let manifest_key = format!("segments/{}/manifest.json", doc_id);
+        let manifest_data = match self.store.get(&manifest_key).await {
+            Ok(data) => data,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let mut manifest: SegmentManifest = serde_json::from_slice(&manifest_data)
+            .map_err(|e| anyhow::anyhow!("failed to parse segment manifest {}: {}", manifest_key, e))?;
+        manifest.segments.sort_by_key(|s| s.start_page);
+
+        let mut chunks = Vec::new();
+        for range in manifest.segments {
+            let compressed = self.store.get(&range.key).await?;
+            let raw = zstd::decode_all(compressed.as_slice())
+                .map_err(|e| anyhow::anyhow!("failed to zstd-decompress segment {}: {}", range.key, e))?;
+
+            let mut cursor = 0usize;
+            while cursor + 4 <= raw.len() {
+                let len = u32::from_le_bytes(raw[cursor..cursor + 4].try_into().unwrap()) as usize;
+                cursor += 4;
+                let record_bytes = raw.get(cursor..cursor + len).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "truncated segment {}: record of {} bytes at offset {} exceeds segment length {}",
+                        range.key,
+                        len,
+                        cursor,
+                        raw.len()
+                    )
+                })?;
+                let record: SegmentPageRecord = serde_json::from_slice(record_bytes)?;
+                cursor += len;
+
+                let mut spans = record.spans;
+                spans.sort_by_key(|s| s.order);
+                let text = spans
+                    .iter()
+                    .map(|s| s.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                chunks.push(Chunk {
+                    id: format!("{}:{}:0", doc_id, record.page_no),
+                    char_count: text.len(),
+                    text,
+                    page_no: record.page_no,
+                    order: 0,
+                });
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    fn compare_chunks(left: &Chunk, right: &Chunk) -> ChunkMatch {
         let left_text = left.text.trim();
         let right_text = right.text.trim();
 
@@ -158,6 +262,92 @@ impl AppState {
         }
     }
 
+    fn chunk_similarity(left: &Chunk, right: &Chunk) -> f64 {
+        let jaro_sim = strsim::jaro_winkler(left.text.trim(), right.text.trim());
+        let normalized_levenshtein = strsim::normalized_levenshtein(left.text.trim(), right.text.trim());
+        (jaro_sim + normalized_levenshtein) / 2.0
+    }
+
+    fn deleted_match(left: &Chunk) -> ChunkMatch {
+        ChunkMatch {
+            left_chunk_id: left.id.clone(),
+            right_chunk_id: String::new(),
+            similarity_score: 0.0,
+            match_type: "deleted".to_string(),
+            left_text: left.text.trim().to_string(),
+            right_text: String::new(),
+            diff_html: format!("<span style='background-color: #ffcccc;'>{}</span>", left.text.trim()),
+        }
+    }
+
+    fn inserted_match(right: &Chunk) -> ChunkMatch {
+        ChunkMatch {
+            left_chunk_id: String::new(),
+            right_chunk_id: right.id.clone(),
+            similarity_score: 0.0,
+            match_type: "inserted".to_string(),
+            left_text: String::new(),
+            right_text: right.text.trim().to_string(),
+            diff_html: format!("<span style='background-color: #ccffcc;'>{}</span>", right.text.trim()),
+        }
+    }
+
+    /// Aligns `left` and `right` chunk sequences with a Needleman-Wunsch
+    /// style global alignment instead of pairing purely by position, so a
+    /// single inserted/deleted paragraph doesn't cascade into false
+    /// mismatches for every chunk that follows it.
+    ///
+    /// This is an associated function rather than a `&self` method (it
+    /// doesn't touch any `AppState` field) so `compare_documents` can move
+    /// owned chunk vectors into `spawn_blocking` and call it there without
+    /// needing `Arc<Self>` or cloning `AppState`.
+    fn align_chunks(left: &[Chunk], right: &[Chunk]) -> Vec<ChunkMatch> {
+        const GAP_PENALTY: f64 = 0.5;
+
+        let n = left.len();
+        let m = right.len();
+
+        // score[i][j] = best alignment score for left[..i] vs right[..j]
+        let mut score = vec![vec![0.0_f64; m + 1]; n + 1];
+        for i in 1..=n {
+            score[i][0] = score[i - 1][0] - GAP_PENALTY;
+        }
+        for j in 1..=m {
+            score[0][j] = score[0][j - 1] - GAP_PENALTY;
+        }
+        for i in 1..=n {
+            for j in 1..=m {
+                let match_score = score[i - 1][j - 1] + Self::chunk_similarity(&left[i - 1], &right[j - 1]);
+                let delete_score = score[i - 1][j] - GAP_PENALTY;
+                let insert_score = score[i][j - 1] - GAP_PENALTY;
+                score[i][j] = match_score.max(delete_score).max(insert_score);
+            }
+        }
+
+        // Traceback from (n, m) to (0, 0), then reverse.
+        let mut matches = Vec::new();
+        let (mut i, mut j) = (n, m);
+        while i > 0 || j > 0 {
+            if i > 0
+                && j > 0
+                && (score[i][j] - (score[i - 1][j - 1] + Self::chunk_similarity(&left[i - 1], &right[j - 1]))).abs() < 1e-9
+            {
+                matches.push(Self::compare_chunks(&left[i - 1], &right[j - 1]));
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && (score[i][j] - (score[i - 1][j] - GAP_PENALTY)).abs() < 1e-9 {
+                matches.push(Self::deleted_match(&left[i - 1]));
+                i -= 1;
+            } else {
+                matches.push(Self::inserted_match(&right[j - 1]));
+                j -= 1;
+            }
+        }
+
+        matches.reverse();
+        matches
+    }
+
     async fn compare_documents(&self, req: CompareRequest) -> Result<CompareResponse> {
         let start = Instant::now();
         let comparison_id = uuid::Uuid::new_v4().to_string();
@@ -166,33 +356,51 @@ impl AppState {
             comparison_id, req.left_doc_id, req.right_doc_id);
 
         info!("Loading chunks from S3 for left document: {}", req.left_doc_id);
-        let left_chunks = self.load_chunks(&req.left_doc_id).await
+        let (left_chunks, left_degraded) = self.load_chunks(&req.left_doc_id).await
             .map_err(|e| {
                 tracing::error!("Failed to load left document chunks: {}", e);
                 e
             })?;
 
         info!("Loading chunks from S3 for right document: {}", req.right_doc_id);
-        let right_chunks = self.load_chunks(&req.right_doc_id).await
+        let (right_chunks, right_degraded) = self.load_chunks(&req.right_doc_id).await
             .map_err(|e| {
                 tracing::error!("Failed to load right document chunks: {}", e);
                 e
             })?;
 
         info!("✓ Loaded {} left chunks and {} right chunks", left_chunks.len(), right_chunks.len());
+        let total_chunks_left = left_chunks.len();
+        let total_chunks_right = right_chunks.len();
 
-        let mut matches = Vec::new();
-
-        // For simplicity, do a pairwise comparison
-        // In production, use more sophisticated alignment algorithms
-        let max_len = std::cmp::max(left_chunks.len(), right_chunks.len());
-
-        for i in 0..max_len {
-            if i < left_chunks.len() && i < right_chunks.len() {
-                let chunk_match = self.compare_chunks(&left_chunks[i], &right_chunks[i]);
-                matches.push(chunk_match);
-            }
+        let mut degraded_doc_ids = Vec::new();
+        if left_degraded {
+            degraded_doc_ids.push(req.left_doc_id.clone());
+        }
+        if right_degraded {
+            degraded_doc_ids.push(req.right_doc_id.clone());
         }
+        if !degraded_doc_ids.is_empty() {
+            tracing::warn!(
+                "Comparison {} used the extractor-segment fallback for {:?}; \
+                 compliance numbers are against unnormalized, page-granularity text",
+                comparison_id, degraded_doc_ids
+            );
+        }
+
+        // Align the two chunk sequences before scoring so an inserted or
+        // deleted paragraph on one side doesn't misalign every pair after
+        // it. The DP fill is O(n*m) and each cell calls the jaro-winkler +
+        // levenshtein blend, so for a few-hundred-page document pair this
+        // is easily tens of millions of string comparisons -- run it on
+        // the blocking thread pool instead of the tokio worker thread so a
+        // large comparison doesn't stall `/health` and every other request
+        // scheduled alongside it.
+        let matches = tokio::task::spawn_blocking(move || {
+            Self::align_chunks(&left_chunks, &right_chunks)
+        })
+        .await
+        .context("alignment task panicked")?;
 
         // Calculate compliance statistics
         let compliant_count = matches.iter()
@@ -216,8 +424,8 @@ impl AppState {
 
         let processing_time_ms = start.elapsed().as_millis();
 
-        Ok(CompareResponse {
-            comparison_id,
+        let response = CompareResponse {
+            comparison_id: comparison_id.clone(),
             left_doc_id: req.left_doc_id,
             right_doc_id: req.right_doc_id,
             matches,
@@ -226,9 +434,69 @@ impl AppState {
             compliant_percentage,
             non_compliant_percentage,
             processing_time_ms,
-            total_chunks_left: left_chunks.len(),
-            total_chunks_right: right_chunks.len(),
-        })
+            total_chunks_left,
+            total_chunks_right,
+            degraded_doc_ids,
+        };
+
+        // Persist the result so it can be retrieved later via a presigned
+        // GET without proxying the (potentially large) payload through us.
+        let result_key = Self::result_key(&comparison_id);
+        let result_data = serde_json::to_vec(&response)?;
+        if let Err(e) = self.store.put(&result_key, result_data).await {
+            tracing::error!("Failed to persist comparison result {}: {}", result_key, e);
+        }
+
+        Ok(response)
+    }
+
+    fn result_key(comparison_id: &str) -> String {
+        format!("results/{}.json", comparison_id)
+    }
+
+    /// Presigning only knows how to sign against `s3_client`, so it isn't
+    /// meaningful for the Azure/GCS/local-filesystem backends the
+    /// `ObjectStore` abstraction also supports: a URL signed against S3
+    /// would point at an endpoint the data was never written to. Fail
+    /// loudly instead of handing back a URL that silently doesn't work.
+    fn require_s3_backend(&self) -> Result<()> {
+        if self.store_backend != "s3" {
+            anyhow::bail!(
+                "presigned URLs require STORE_BACKEND=s3, but it is set to {}",
+                self.store_backend
+            );
+        }
+        Ok(())
+    }
+
+    /// Generates a presigned PUT URL for a client to upload a raw PDF page
+    /// straight to object storage, bypassing this service for the bytes.
+    async fn presign_upload(&self, doc_id: &str, page: i32, expires_in: Duration) -> Result<String> {
+        self.require_s3_backend()?;
+        let key = format!("raw/{}/{}.pdf", doc_id, page);
+        let presigned = self
+            .s3_client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(presigned.uri().to_string())
+    }
+
+    /// Generates a presigned GET URL for fetching a previously computed
+    /// `CompareResponse` directly from object storage.
+    async fn presign_result_download(&self, comparison_id: &str, expires_in: Duration) -> Result<String> {
+        self.require_s3_backend()?;
+        let key = Self::result_key(comparison_id);
+        let presigned = self
+            .s3_client
+            .get_object()
+            .bucket(&self.bucket_name)
+            .key(&key)
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(presigned.uri().to_string())
     }
 }
 
@@ -246,6 +514,52 @@ async fn compare(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
+const PRESIGNED_URL_EXPIRY: Duration = Duration::from_secs(900);
+
+#[derive(Debug, Deserialize)]
+struct UploadRequest {
+    doc_id: String,
+    page: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct UploadResponse {
+    upload_url: String,
+    key: String,
+}
+
+async fn create_upload_url(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<UploadRequest>,
+) -> Result<Json<UploadResponse>, (StatusCode, String)> {
+    let upload_url = state
+        .presign_upload(&req.doc_id, req.page, PRESIGNED_URL_EXPIRY)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(UploadResponse {
+        upload_url,
+        key: format!("raw/{}/{}.pdf", req.doc_id, req.page),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+struct ResultDownloadResponse {
+    download_url: String,
+}
+
+async fn result_download_url(
+    State(state): State<Arc<AppState>>,
+    Path(comparison_id): Path<String>,
+) -> Result<Json<ResultDownloadResponse>, (StatusCode, String)> {
+    let download_url = state
+        .presign_result_download(&comparison_id, PRESIGNED_URL_EXPIRY)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ResultDownloadResponse { download_url }))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     println!("=== RUST COMPARATOR STARTING ===");
@@ -260,20 +574,10 @@ async fn main() -> Result<()> {
         .unwrap_or_else(|_| "http://minio:9000".to_string());
     let bucket_name = std::env::var("S3_BUCKET")
         .unwrap_or_else(|_| "documents".to_string());
-    let access_key = std::env::var("AWS_ACCESS_KEY_ID")
-        .unwrap_or_else(|_| "minio".to_string());
-    let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY")
-        .unwrap_or_else(|_| "minio123".to_string());
 
     info!("Configuration loaded - S3: {}, Bucket: {}", s3_endpoint, bucket_name);
 
-    let credentials = aws_sdk_s3::config::Credentials::new(
-        access_key,
-        secret_key,
-        None,
-        None,
-        "static",
-    );
+    let credentials = build_credentials_chain().await;
 
     let s3_config = aws_sdk_s3::config::Builder::new()
         .endpoint_url(&s3_endpoint)
@@ -285,14 +589,22 @@ async fn main() -> Result<()> {
     let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
     info!("S3 client initialized successfully");
 
+    let store_backend = store::backend_name();
+    let store = store::build_store(s3_client.clone(), bucket_name.clone()).await?;
+    info!("Object store backend initialized: {}", store_backend);
+
     let state = Arc::new(AppState {
+        store,
         s3_client,
         bucket_name,
+        store_backend,
     });
 
     let app = Router::new()
         .route("/health", get(health))
         .route("/compare", post(compare))
+        .route("/uploads", post(create_upload_url))
+        .route("/results/:comparison_id/download", get(result_download_url))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
@@ -311,3 +623,148 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &str, text: &str) -> Chunk {
+        Chunk {
+            id: id.to_string(),
+            text: text.to_string(),
+            page_no: 0,
+            order: 0,
+            char_count: text.len(),
+        }
+    }
+
+    #[test]
+    fn align_chunks_both_empty_produces_no_matches() {
+        let matches = AppState::align_chunks(&[], &[]);
+        assert!(matches.is_empty());
+    }
+
+    #[test]
+    fn align_chunks_left_empty_is_all_insertions() {
+        let right = vec![chunk("r0", "alpha"), chunk("r1", "beta")];
+        let matches = AppState::align_chunks(&[], &right);
+
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.match_type == "inserted"));
+        assert_eq!(matches[0].right_chunk_id, "r0");
+        assert_eq!(matches[1].right_chunk_id, "r1");
+    }
+
+    #[test]
+    fn align_chunks_right_empty_is_all_deletions() {
+        let left = vec![chunk("l0", "alpha"), chunk("l1", "beta")];
+        let matches = AppState::align_chunks(&left, &[]);
+
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.match_type == "deleted"));
+        assert_eq!(matches[0].left_chunk_id, "l0");
+        assert_eq!(matches[1].left_chunk_id, "l1");
+    }
+
+    #[test]
+    fn align_chunks_identical_sequences_are_all_exact_matches() {
+        let left = vec![chunk("l0", "alpha"), chunk("l1", "beta"), chunk("l2", "gamma")];
+        let right = vec![chunk("r0", "alpha"), chunk("r1", "beta"), chunk("r2", "gamma")];
+
+        let matches = AppState::align_chunks(&left, &right);
+
+        assert_eq!(matches.len(), 3);
+        for (m, (l, r)) in matches.iter().zip(left.iter().zip(right.iter())) {
+            assert_eq!(m.match_type, "exact");
+            assert_eq!(m.left_chunk_id, l.id);
+            assert_eq!(m.right_chunk_id, r.id);
+        }
+    }
+
+    /// A single inserted paragraph should surface as one `inserted` entry
+    /// at its position, not cascade into mismatches for every chunk after
+    /// it the way naive positional pairing would.
+    #[test]
+    fn align_chunks_handles_single_insertion_without_cascading() {
+        let left = vec![chunk("l0", "alpha"), chunk("l1", "beta")];
+        let right = vec![
+            chunk("r0", "alpha"),
+            chunk("r1", "inserted paragraph"),
+            chunk("r2", "beta"),
+        ];
+
+        let matches = AppState::align_chunks(&left, &right);
+
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].match_type, "exact");
+        assert_eq!(matches[0].left_chunk_id, "l0");
+        assert_eq!(matches[0].right_chunk_id, "r0");
+
+        assert_eq!(matches[1].match_type, "inserted");
+        assert_eq!(matches[1].right_chunk_id, "r1");
+
+        assert_eq!(matches[2].match_type, "exact");
+        assert_eq!(matches[2].left_chunk_id, "l1");
+        assert_eq!(matches[2].right_chunk_id, "r2");
+    }
+
+    /// Same as above but for a deletion, so the alignment is exercised in
+    /// both directions rather than just the insertion case.
+    #[test]
+    fn align_chunks_handles_single_deletion_without_cascading() {
+        let left = vec![
+            chunk("l0", "alpha"),
+            chunk("l1", "deleted paragraph"),
+            chunk("l2", "beta"),
+        ];
+        let right = vec![chunk("r0", "alpha"), chunk("r1", "beta")];
+
+        let matches = AppState::align_chunks(&left, &right);
+
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].match_type, "exact");
+        assert_eq!(matches[0].left_chunk_id, "l0");
+
+        assert_eq!(matches[1].match_type, "deleted");
+        assert_eq!(matches[1].left_chunk_id, "l1");
+
+        assert_eq!(matches[2].match_type, "exact");
+        assert_eq!(matches[2].left_chunk_id, "l2");
+    }
+
+    /// Every left chunk must appear exactly once (as a match or a
+    /// deletion) and every right chunk exactly once (as a match or an
+    /// insertion): the alignment can't drop or duplicate input chunks no
+    /// matter how the DP ties are broken.
+    #[test]
+    fn align_chunks_accounts_for_every_input_chunk_exactly_once() {
+        let left = vec![
+            chunk("l0", "one"),
+            chunk("l1", "two"),
+            chunk("l2", "three"),
+        ];
+        let right = vec![
+            chunk("r0", "one"),
+            chunk("r1", "two point five"),
+            chunk("r2", "three"),
+        ];
+
+        let matches = AppState::align_chunks(&left, &right);
+
+        let mut left_seen: Vec<&str> = matches
+            .iter()
+            .filter(|m| !m.left_chunk_id.is_empty())
+            .map(|m| m.left_chunk_id.as_str())
+            .collect();
+        let mut right_seen: Vec<&str> = matches
+            .iter()
+            .filter(|m| !m.right_chunk_id.is_empty())
+            .map(|m| m.right_chunk_id.as_str())
+            .collect();
+        left_seen.sort();
+        right_seen.sort();
+
+        assert_eq!(left_seen, vec!["l0", "l1", "l2"]);
+        assert_eq!(right_seen, vec!["r0", "r1", "r2"]);
+    }
+}
@@ -1,21 +1,41 @@
 use anyhow::{Context, Result};
-use futures_util::StreamExt;
+use futures_util::{StreamExt, TryStreamExt};
 use lapin::{
-    options::*, types::FieldTable, BasicProperties, Channel, Connection, ConnectionProperties,
+    options::*,
+    types::{AMQPValue, FieldTable},
+    BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
-#[derive(Debug, Deserialize, Serialize)]
+mod store;
+use common::build_credentials_chain;
+use store::{is_conflict, ObjectStore};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 struct PageReadyMessage {
     doc_id: String,
     page_no: i32,
     s3_uri: String,
     sha256: String,
+    /// Total page count for the document this page belongs to, set by the
+    /// orchestrator (which already knows it from fanning out `page_no`).
+    /// Lets the segment buffer below recognize the last page of a document
+    /// and flush its trailing partial segment instead of holding it forever.
+    total_pages: i32,
 }
 
 #[derive(Debug, Serialize)]
+struct DlqMessage {
+    original_queue: String,
+    payload: String,
+    error: String,
+    attempts: u32,
+    failed_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct Span {
     text: String,
     x: f32,
@@ -35,10 +55,277 @@ struct PageExtractedMessage {
     extracted_at: String,
 }
 
+/// One page's worth of spans, buffered in memory until its segment is
+/// flushed to S3.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct PageRecord {
+    page_no: i32,
+    spans: Vec<Span>,
+}
+
+/// Describes one flushed segment so readers can fetch only the page ranges
+/// they need without downloading the whole document.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SegmentRange {
+    start_page: i32,
+    end_page: i32,
+    key: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+struct SegmentManifest {
+    segments: Vec<SegmentRange>,
+}
+
+/// Target sizes that trigger a segment flush: whichever threshold is hit
+/// first for a given document closes out its current buffer.
+const SEGMENT_SPAN_THRESHOLD: usize = 20_000;
+const SEGMENT_PAGE_THRESHOLD: usize = 20;
+
+/// Delivery attempts for `process_message` before a `page.ready` message is
+/// given up on and routed to the dead-letter queue. Backoff doubles each
+/// attempt starting from `RETRY_BASE_BACKOFF`.
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+const RETRY_BASE_BACKOFF: Duration = Duration::from_millis(200);
+const DLQ_QUEUE: &str = "page.ready.dlq";
+
+/// Attempts for the manifest's conditional-write retry loop in
+/// `SegmentBuffer::append_to_manifest` before giving up on a losing race
+/// against other replicas' flushes.
+const MANIFEST_CAS_MAX_ATTEMPTS: u32 = 10;
+
+/// Batches pages into zstd-compressed segments in the object store. Kept as
+/// its own struct borrowing only the `ObjectStore` (not the whole
+/// `Extractor`, which also owns a live AMQP connection) so this logic can be
+/// exercised directly in tests against an in-memory store.
+struct SegmentBuffer<'a> {
+    store: &'a dyn ObjectStore,
+}
+
+impl<'a> SegmentBuffer<'a> {
+    fn manifest_key(doc_id: &str) -> String {
+        format!("segments/{}/manifest.json", doc_id)
+    }
+
+    fn pending_prefix(doc_id: &str) -> String {
+        format!("segments/{}/pending/", doc_id)
+    }
+
+    fn pending_key(doc_id: &str, page_no: i32) -> String {
+        format!("{}{:06}.json", Self::pending_prefix(doc_id), page_no)
+    }
+
+    /// Unlike `pending/`, these markers are never deleted once a page has
+    /// been durably buffered, so counting them is a high-water mark of
+    /// every distinct page ever seen for `doc_id` -- not just whatever
+    /// still happens to be sitting in `pending/` at this moment.
+    fn seen_prefix(doc_id: &str) -> String {
+        format!("segments/{}/seen/", doc_id)
+    }
+
+    fn seen_key(doc_id: &str, page_no: i32) -> String {
+        format!("{}{:06}", Self::seen_prefix(doc_id), page_no)
+    }
+
+    /// Returns the manifest alongside its current ETag (`None` if it
+    /// doesn't exist yet), so callers can feed the ETag into
+    /// `put_if_match` and detect a concurrent writer instead of silently
+    /// overwriting it.
+    async fn load_manifest(&self, doc_id: &str) -> Result<(SegmentManifest, Option<String>)> {
+        match self.store.get_with_etag(&Self::manifest_key(doc_id)).await {
+            Ok((data, etag)) => Ok((serde_json::from_slice(&data).unwrap_or_default(), Some(etag))),
+            Err(_) => Ok((SegmentManifest::default(), None)),
+        }
+    }
+
+    /// Concatenates buffered pages into length-prefixed JSON records,
+    /// zstd-compresses the result, and uploads it as one segment object
+    /// named for the half-open page range it covers. Updates the
+    /// document's manifest so readers can fetch only the ranges they need.
+    ///
+    /// The manifest update is an upsert keyed on the segment's object key:
+    /// `process_with_retry` can re-run this after the segment object itself
+    /// was already uploaded (e.g. a later step like the `page.extracted`
+    /// publish failed and the whole message is retried), and the page range
+    /// a given buffer flushes to is deterministic, so a retry recomputes
+    /// the exact same key. Skipping the append when it's already present
+    /// keeps that retry from appending a duplicate `SegmentRange` that
+    /// would make `load_segment_chunks` double-count those pages.
+    async fn flush_segment(&self, doc_id: &str, pages: Vec<PageRecord>) -> Result<()> {
+        if pages.is_empty() {
+            return Ok(());
+        }
+
+        let start_page = pages.first().unwrap().page_no;
+        let end_page = pages.last().unwrap().page_no + 1;
+
+        let key = format!(
+            "segments/{}/{:06}-{:06}.json.zst",
+            doc_id, start_page, end_page
+        );
+
+        let (manifest, etag) = self.load_manifest(doc_id).await?;
+        if manifest.segments.iter().any(|s| s.key == key) {
+            info!(
+                "Segment {} for doc {} already recorded in manifest, skipping re-flush",
+                key, doc_id
+            );
+            return Ok(());
+        }
+
+        let mut raw = Vec::new();
+        for page in &pages {
+            let record = serde_json::to_vec(page)?;
+            raw.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            raw.extend_from_slice(&record);
+        }
+
+        let compressed = zstd::encode_all(raw.as_slice(), 0)
+            .context("failed to zstd-compress segment")?;
+        self.store.put(&key, compressed).await?;
+
+        self.append_to_manifest(doc_id, &key, start_page, end_page, manifest, etag)
+            .await?;
+
+        info!(
+            "Flushed segment {} covering pages [{}, {}) for doc {}",
+            key, start_page, end_page, doc_id
+        );
+        Ok(())
+    }
+
+    /// Appends `key`'s page range to the document's manifest with a
+    /// conditional write keyed on the manifest's current ETag, reloading
+    /// and retrying the whole read-modify-write whenever another replica's
+    /// concurrent flush wins the race. Without this, two replicas that both
+    /// cross the flush threshold around the same time can both read the
+    /// same manifest, both append their own segment, and the second's
+    /// blind `put` silently overwrites the first's entry -- losing that
+    /// segment from the manifest even though its object was durably
+    /// uploaded.
+    async fn append_to_manifest(
+        &self,
+        doc_id: &str,
+        key: &str,
+        start_page: i32,
+        end_page: i32,
+        mut manifest: SegmentManifest,
+        mut etag: Option<String>,
+    ) -> Result<()> {
+        for _ in 0..MANIFEST_CAS_MAX_ATTEMPTS {
+            if manifest.segments.iter().any(|s| s.key == key) {
+                return Ok(());
+            }
+
+            manifest.segments.push(SegmentRange {
+                start_page,
+                end_page,
+                key: key.to_string(),
+            });
+            manifest.segments.sort_by_key(|s| s.start_page);
+            let manifest_data = serde_json::to_vec(&manifest)?;
+
+            match self
+                .store
+                .put_if_match(&Self::manifest_key(doc_id), manifest_data, etag.as_deref())
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(e) if is_conflict(&e) => {
+                    let (latest, latest_etag) = self.load_manifest(doc_id).await?;
+                    manifest = latest;
+                    etag = latest_etag;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        anyhow::bail!(
+            "exhausted {} attempts updating manifest for doc {} (key {})",
+            MANIFEST_CAS_MAX_ATTEMPTS,
+            doc_id,
+            key
+        )
+    }
+
+    /// Buffers `page`'s spans for `doc_id` and flushes a segment once the
+    /// buffer reaches the configured span/page count target, or once
+    /// `total_pages` distinct pages have ever been seen for the document
+    /// (the trailing partial segment, which otherwise never crosses a
+    /// threshold). That last-page check is driven by the durable marker in
+    /// `segments/{doc_id}/seen/`, not by how many records currently sit in
+    /// `pending/`: a page redelivered after its document's trailing segment
+    /// already flushed would otherwise land in a fresh, small `pending/`
+    /// listing that never again reaches `total_pages`, orphaning it there
+    /// forever instead of reaching the manifest.
+    ///
+    /// `page.ready` is a competing-consumers queue, so pages of the same
+    /// `doc_id` can be delivered to any extractor replica — a buffer kept
+    /// in process-local memory would never see more than one replica's
+    /// share of a document, the trailing partial segment would never
+    /// flush, and a crash would silently drop pages that were already
+    /// acked as processed. Instead the buffer itself lives in the object
+    /// store, under `segments/{doc_id}/pending/`, as one small object per
+    /// page: writing it here is what makes the page durable, and every
+    /// replica lists the same prefix to see the whole document's progress
+    /// regardless of which replica handled which page.
+    ///
+    /// Two replicas can still both observe the threshold crossed and both
+    /// flush overlapping segments; `append_to_manifest`'s conditional write
+    /// is what keeps the second flush's manifest update from silently
+    /// discarding the first's instead of merging with it.
+    async fn buffer_page(&self, doc_id: &str, page: PageRecord, total_pages: i32) -> Result<()> {
+        let page_key = Self::pending_key(doc_id, page.page_no);
+        let page_payload = serde_json::to_vec(&page)?;
+        self.store.put(&page_key, page_payload).await?;
+        self.store.put(&Self::seen_key(doc_id, page.page_no), Vec::new()).await?;
+
+        let pending_keys: Vec<String> = self
+            .store
+            .list(&Self::pending_prefix(doc_id))
+            .try_collect()
+            .await?;
+
+        let mut pages = Vec::with_capacity(pending_keys.len());
+        for key in &pending_keys {
+            let data = self.store.get(key).await?;
+            pages.push(serde_json::from_slice::<PageRecord>(&data)?);
+        }
+        pages.sort_by_key(|p| p.page_no);
+
+        let seen_count: usize = self
+            .store
+            .list(&Self::seen_prefix(doc_id))
+            .try_collect::<Vec<String>>()
+            .await?
+            .len();
+
+        let span_count: usize = pages.iter().map(|p| p.spans.len()).sum();
+        let is_last_page = seen_count >= total_pages.max(0) as usize;
+        if span_count < SEGMENT_SPAN_THRESHOLD
+            && pages.len() < SEGMENT_PAGE_THRESHOLD
+            && !is_last_page
+        {
+            return Ok(());
+        }
+
+        self.flush_segment(doc_id, pages).await?;
+
+        // Only reap the pending objects after the segment they came from
+        // has been durably uploaded, so a failed flush above leaves them
+        // in place for the next page (this retry, or a sibling page on any
+        // replica) to try flushing again instead of losing them.
+        for key in &pending_keys {
+            self.store.delete(key).await?;
+        }
+        Ok(())
+    }
+}
+
 struct Extractor {
     _connection: Connection,
     channel: Channel,
-    s3_client: aws_sdk_s3::Client,
+    store: Box<dyn ObjectStore>,
     bucket_name: String,
 }
 
@@ -77,20 +364,23 @@ impl Extractor {
                 FieldTable::default(),
             )
             .await?;
+
+        info!("Declaring queue: {}", DLQ_QUEUE);
+        channel
+            .queue_declare(
+                DLQ_QUEUE,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
         info!("✓ Queues declared successfully");
 
         // Configure S3 client
-        let access_key = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_else(|_| "minio".to_string());
-        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_else(|_| "minio123".to_string());
-
         info!("Initializing S3 client for endpoint: {}", s3_endpoint);
-        let credentials = aws_sdk_s3::config::Credentials::new(
-            access_key,
-            secret_key,
-            None,
-            None,
-            "static",
-        );
+        let credentials = build_credentials_chain().await;
 
         let s3_config = aws_sdk_s3::config::Builder::new()
             .endpoint_url(s3_endpoint)
@@ -102,10 +392,13 @@ impl Extractor {
         let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
         info!("✓ S3 client initialized");
 
+        let store = store::build_store(s3_client, bucket_name.clone()).await?;
+        info!("✓ Object store backend initialized");
+
         Ok(Self {
             _connection: conn,
             channel,
-            s3_client,
+            store,
             bucket_name,
         })
     }
@@ -142,31 +435,24 @@ impl Extractor {
     }
 
     async fn download_from_s3(&self, s3_uri: &str) -> Result<Vec<u8>> {
-        let key = s3_uri.trim_start_matches("s3://").trim_start_matches(&format!("{}/", self.bucket_name));
-
-        let resp = self.s3_client
-            .get_object()
-            .bucket(&self.bucket_name)
-            .key(key)
-            .send()
-            .await?;
-
-        let data = resp.body.collect().await?;
-        Ok(data.into_bytes().to_vec())
+        let key = store::key_from_uri(s3_uri, &self.bucket_name);
+        self.store.get(&key).await
     }
 
     async fn upload_to_s3(&self, key: &str, data: &[u8]) -> Result<String> {
-        self.s3_client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(key)
-            .body(data.to_vec().into())
-            .send()
-            .await?;
-
+        self.store.put(key, data.to_vec()).await?;
         Ok(format!("s3://{}/{}", self.bucket_name, key))
     }
 
+    /// Borrows just the object store, not the live AMQP connection/channel,
+    /// so the segment-buffering logic can be unit tested against an
+    /// in-memory `ObjectStore` without standing up a broker.
+    fn segment_buffer(&self) -> SegmentBuffer<'_> {
+        SegmentBuffer {
+            store: self.store.as_ref(),
+        }
+    }
+
     async fn process_message(&self, msg: PageReadyMessage) -> Result<()> {
         let start = Instant::now();
         info!("Processing page {} of doc {}", msg.page_no, msg.doc_id);
@@ -177,10 +463,24 @@ impl Extractor {
         // Extract text
         let spans = self.extract_text_from_pdf(&pdf_bytes).await?;
 
-        // Store extracted data
-        let json_data = serde_json::to_vec(&spans)?;
-        let json_key = format!("pages/{}/{}.json", msg.doc_id, msg.page_no);
-        let json_uri = self.upload_to_s3(&json_key, &json_data).await?;
+        // Buffer the page for batched, compressed segment storage instead
+        // of writing one small JSON object per page.
+        self.segment_buffer()
+            .buffer_page(
+                &msg.doc_id,
+                PageRecord {
+                    page_no: msg.page_no,
+                    spans: spans.clone(),
+                },
+                msg.total_pages,
+            )
+            .await?;
+        let json_uri = format!(
+            "s3://{}/{}#page={}",
+            self.bucket_name,
+            SegmentBuffer::manifest_key(&msg.doc_id),
+            msg.page_no
+        );
 
         // Publish extracted message
         let extracted_msg = PageExtractedMessage {
@@ -209,6 +509,58 @@ impl Extractor {
         Ok(())
     }
 
+    /// Retries `process_message` with exponential backoff, giving up after
+    /// `MAX_DELIVERY_ATTEMPTS` so a transient S3/broker hiccup doesn't loop
+    /// forever. Returns the last error (and the attempt it failed on) once
+    /// attempts are exhausted.
+    async fn process_with_retry(&self, msg: &PageReadyMessage) -> std::result::Result<(), (anyhow::Error, u32)> {
+        let mut attempt = 1;
+        loop {
+            match self.process_message(msg.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= MAX_DELIVERY_ATTEMPTS => return Err((e, attempt)),
+                Err(e) => {
+                    let backoff = RETRY_BASE_BACKOFF * 2u32.pow(attempt - 1);
+                    warn!(
+                        "Attempt {}/{} failed for page {} of doc {}: {}. Retrying in {:?}",
+                        attempt, MAX_DELIVERY_ATTEMPTS, msg.page_no, msg.doc_id, e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Publishes an undeliverable `page.ready` payload to the dead-letter
+    /// queue, carrying the failure reason and the number of attempts made so
+    /// operators have a recoverable audit trail instead of a silently
+    /// dropped page.
+    async fn publish_to_dlq(&self, payload: &[u8], error: &str, attempts: u32) -> Result<()> {
+        let dlq_msg = DlqMessage {
+            original_queue: "page.ready".to_string(),
+            payload: String::from_utf8_lossy(payload).to_string(),
+            error: error.to_string(),
+            attempts,
+            failed_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let body = serde_json::to_vec(&dlq_msg)?;
+
+        let mut headers = FieldTable::default();
+        headers.insert("x-attempts".into(), AMQPValue::LongUInt(attempts));
+
+        self.channel
+            .basic_publish(
+                "",
+                DLQ_QUEUE,
+                BasicPublishOptions::default(),
+                &body,
+                BasicProperties::default().with_headers(headers),
+            )
+            .await?;
+        Ok(())
+    }
+
     async fn start(&self) -> Result<()> {
         info!("Starting extractor worker, subscribing to queue: page.ready");
 
@@ -233,14 +585,26 @@ impl Extractor {
                 match serde_json::from_slice::<PageReadyMessage>(&delivery.data) {
                     Ok(msg) => {
                         info!("Received page.ready message for doc: {}, page: {}", msg.doc_id, msg.page_no);
-                        if let Err(e) = self.process_message(msg).await {
-                            error!("Error processing message: {}", e);
+                        if let Err((e, attempts)) = self.process_with_retry(&msg).await {
+                            error!(
+                                "Giving up on page {} of doc {} after {} attempts: {}",
+                                msg.page_no, msg.doc_id, attempts, e
+                            );
+                            if let Err(dlq_err) = self.publish_to_dlq(&delivery.data, &e.to_string(), attempts).await {
+                                error!("Failed to publish page {} of doc {} to {}: {}", msg.page_no, msg.doc_id, DLQ_QUEUE, dlq_err);
+                            }
                         }
                         delivery.ack(BasicAckOptions::default()).await?;
                     }
                     Err(e) => {
-                        error!("Failed to deserialize message: {}", e);
-                        delivery.nack(BasicNackOptions::default()).await?;
+                        // Malformed messages can never succeed on redelivery,
+                        // so route them straight to the DLQ instead of
+                        // nacking them back onto the queue forever.
+                        error!("Failed to deserialize message, routing to {}: {}", DLQ_QUEUE, e);
+                        if let Err(dlq_err) = self.publish_to_dlq(&delivery.data, &e.to_string(), 0).await {
+                            error!("Failed to publish malformed message to {}: {}", DLQ_QUEUE, dlq_err);
+                        }
+                        delivery.ack(BasicAckOptions::default()).await?;
                     }
                 }
             }
@@ -279,3 +643,249 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures_util::stream::BoxStream;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    /// In-memory `ObjectStore` so `SegmentBuffer` can be exercised without a
+    /// real S3/MinIO endpoint. Each entry's ETag is just its write
+    /// generation as a string, which is all `put_if_match`'s
+    /// compare-and-swap semantics need.
+    struct MemoryStore {
+        objects: Mutex<BTreeMap<String, (Vec<u8>, u64)>>,
+    }
+
+    impl MemoryStore {
+        fn new() -> Self {
+            Self {
+                objects: Mutex::new(BTreeMap::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ObjectStore for MemoryStore {
+        async fn get(&self, key: &str) -> Result<Vec<u8>> {
+            self.objects
+                .lock()
+                .unwrap()
+                .get(key)
+                .map(|(bytes, _)| bytes.clone())
+                .ok_or_else(|| anyhow::anyhow!("no such key: {}", key))
+        }
+
+        async fn get_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+            let bytes = self.get(key).await?;
+            Ok(bytes[start as usize..(end as usize).min(bytes.len())].to_vec())
+        }
+
+        async fn size(&self, key: &str) -> Result<u64> {
+            Ok(self.get(key).await?.len() as u64)
+        }
+
+        async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<()> {
+            let mut objects = self.objects.lock().unwrap();
+            let next_gen = objects.get(key).map(|(_, gen)| gen + 1).unwrap_or(0);
+            objects.insert(key.to_string(), (bytes, next_gen));
+            Ok(())
+        }
+
+        async fn get_with_etag(&self, key: &str) -> Result<(Vec<u8>, String)> {
+            self.objects
+                .lock()
+                .unwrap()
+                .get(key)
+                .map(|(bytes, gen)| (bytes.clone(), gen.to_string()))
+                .ok_or_else(|| anyhow::anyhow!("no such key: {}", key))
+        }
+
+        async fn put_if_match(
+            &self,
+            key: &str,
+            bytes: Vec<u8>,
+            expected_etag: Option<&str>,
+        ) -> Result<String> {
+            let mut objects = self.objects.lock().unwrap();
+            let current_gen = objects.get(key).map(|(_, gen)| *gen);
+            let matches = match (expected_etag, current_gen) {
+                (None, None) => true,
+                (Some(expected), Some(gen)) => expected == gen.to_string(),
+                _ => false,
+            };
+            if !matches {
+                return Err(common::object_store::ConflictError {
+                    key: key.to_string(),
+                    expected: expected_etag.map(|s| s.to_string()),
+                }
+                .into());
+            }
+            let next_gen = current_gen.map(|gen| gen + 1).unwrap_or(0);
+            objects.insert(key.to_string(), (bytes, next_gen));
+            Ok(next_gen.to_string())
+        }
+
+        async fn delete(&self, key: &str) -> Result<()> {
+            self.objects.lock().unwrap().remove(key);
+            Ok(())
+        }
+
+        fn list(&self, prefix: &str) -> BoxStream<'_, Result<String>> {
+            let prefix = prefix.to_string();
+            let keys: Vec<String> = self
+                .objects
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|k| k.starts_with(&prefix))
+                .cloned()
+                .collect();
+            futures_util::stream::iter(keys.into_iter().map(Ok)).boxed()
+        }
+    }
+
+    fn page(page_no: i32, text: &str) -> PageRecord {
+        PageRecord {
+            page_no,
+            spans: vec![Span {
+                text: text.to_string(),
+                x: 0.0,
+                y: 0.0,
+                w: 0.0,
+                h: 0.0,
+                order: 0,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn buffer_page_flushes_trailing_partial_via_total_pages() {
+        let store = MemoryStore::new();
+        let buffer = SegmentBuffer { store: &store };
+
+        buffer.buffer_page("doc-1", page(0, "hello"), 1).await.unwrap();
+
+        let (manifest, _) = buffer.load_manifest("doc-1").await.unwrap();
+        assert_eq!(manifest.segments.len(), 1);
+        assert_eq!(manifest.segments[0].start_page, 0);
+        assert_eq!(manifest.segments[0].end_page, 1);
+
+        let pending: Vec<String> = store
+            .list(&SegmentBuffer::pending_prefix("doc-1"))
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(pending.is_empty(), "flushed pages should be reaped from pending/");
+    }
+
+    #[tokio::test]
+    async fn buffer_page_flushes_once_page_count_threshold_is_crossed() {
+        let store = MemoryStore::new();
+        let buffer = SegmentBuffer { store: &store };
+        let total_pages = 1_000; // far above what's buffered, so only the count threshold matters
+
+        for page_no in 0..(SEGMENT_PAGE_THRESHOLD as i32 - 1) {
+            buffer.buffer_page("doc-2", page(page_no, ""), total_pages).await.unwrap();
+        }
+        let (manifest, _) = buffer.load_manifest("doc-2").await.unwrap();
+        assert!(
+            manifest.segments.is_empty(),
+            "should not flush before the page-count threshold is crossed"
+        );
+
+        let last_page_no = SEGMENT_PAGE_THRESHOLD as i32 - 1;
+        buffer
+            .buffer_page("doc-2", page(last_page_no, ""), total_pages)
+            .await
+            .unwrap();
+
+        let (manifest, _) = buffer.load_manifest("doc-2").await.unwrap();
+        assert_eq!(manifest.segments.len(), 1);
+        assert_eq!(manifest.segments[0].start_page, 0);
+        assert_eq!(manifest.segments[0].end_page, SEGMENT_PAGE_THRESHOLD as i32);
+    }
+
+    /// Simulates `process_with_retry` re-running `buffer_page` from scratch
+    /// after a later step (e.g. the `page.extracted` publish) failed: the
+    /// manifest must not end up with two entries for the same page.
+    #[tokio::test]
+    async fn buffer_page_retry_does_not_duplicate_manifest_entry() {
+        let store = MemoryStore::new();
+        let buffer = SegmentBuffer { store: &store };
+
+        buffer.buffer_page("doc-3", page(0, "x"), 1).await.unwrap();
+        buffer.buffer_page("doc-3", page(0, "x"), 1).await.unwrap();
+
+        let (manifest, _) = buffer.load_manifest("doc-3").await.unwrap();
+        assert_eq!(manifest.segments.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn flush_segment_byte_format_round_trips() {
+        let store = MemoryStore::new();
+        let buffer = SegmentBuffer { store: &store };
+        let pages = vec![page(0, "alpha"), page(1, "beta")];
+
+        buffer.flush_segment("doc-4", pages).await.unwrap();
+
+        let (manifest, _) = buffer.load_manifest("doc-4").await.unwrap();
+        let key = &manifest.segments[0].key;
+        let compressed = store.get(key).await.unwrap();
+        let raw = zstd::decode_all(compressed.as_slice()).unwrap();
+
+        let mut cursor = 0usize;
+        let mut decoded = Vec::new();
+        while cursor + 4 <= raw.len() {
+            let len = u32::from_le_bytes(raw[cursor..cursor + 4].try_into().unwrap()) as usize;
+            cursor += 4;
+            let record: PageRecord = serde_json::from_slice(&raw[cursor..cursor + len]).unwrap();
+            cursor += len;
+            decoded.push(record);
+        }
+
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].page_no, 0);
+        assert_eq!(decoded[0].spans[0].text, "alpha");
+        assert_eq!(decoded[1].page_no, 1);
+        assert_eq!(decoded[1].spans[0].text, "beta");
+    }
+
+    /// Simulates two replicas racing to flush different segments for the
+    /// same document: the second `append_to_manifest` call is handed a
+    /// stale ETag (the manifest moved under it), so it must reload and
+    /// retry instead of clobbering the first replica's entry.
+    #[tokio::test]
+    async fn append_to_manifest_retries_on_concurrent_writer() {
+        let store = MemoryStore::new();
+        let buffer = SegmentBuffer { store: &store };
+
+        let (manifest, etag) = buffer.load_manifest("doc-5").await.unwrap();
+        assert!(etag.is_none(), "manifest shouldn't exist yet");
+
+        // A "concurrent" replica flushes its own segment first, advancing
+        // the manifest's ETag out from under the stale (manifest, etag)
+        // pair captured above.
+        buffer
+            .append_to_manifest("doc-5", "segments/doc-5/000000-000010.json.zst", 0, 10, manifest.clone(), etag.clone())
+            .await
+            .unwrap();
+
+        // This call still holds the pre-write manifest/etag, so its first
+        // put_if_match attempt must conflict and it must retry against the
+        // now-current manifest rather than returning an error or silently
+        // overwriting the first replica's entry.
+        buffer
+            .append_to_manifest("doc-5", "segments/doc-5/000010-000020.json.zst", 10, 20, manifest, etag)
+            .await
+            .unwrap();
+
+        let (final_manifest, _) = buffer.load_manifest("doc-5").await.unwrap();
+        assert_eq!(final_manifest.segments.len(), 2);
+        assert_eq!(final_manifest.segments[0].start_page, 0);
+        assert_eq!(final_manifest.segments[1].start_page, 10);
+    }
+}
@@ -0,0 +1,6 @@
+//! Re-exports the `ObjectStore` abstraction, its S3/Azure/GCS/local-disk
+//! backends, and the retry-with-backoff wrapper around them from
+//! `services/common`, where they're shared across every service so a fix
+//! (like the retry logic) lands once; this module just exposes them under
+//! the name the rest of this crate imports from.
+pub use common::object_store::{build_store, is_conflict, key_from_uri, ObjectStore};
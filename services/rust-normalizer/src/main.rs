@@ -4,10 +4,18 @@ use lapin::{
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 use unicode_normalization::UnicodeNormalization;
 
+mod store;
+use common::build_credentials_chain;
+use store::ObjectStore;
+
+/// Default lifetime for a presigned GET URL when `PRESIGN_EXPIRY_SECS` isn't
+/// set.
+const DEFAULT_PRESIGN_EXPIRY: Duration = Duration::from_secs(900);
+
 #[derive(Debug, Deserialize)]
 struct Span {
     text: String,
@@ -42,12 +50,23 @@ struct PageChunkedMessage {
     page_no: i32,
     chunks: Vec<Chunk>,
     chunk_uri: String,
+    /// Time-limited presigned GET URL for `chunk_uri`, set when
+    /// `PRESIGN_URLS=true` so stateless downstream consumers can fetch the
+    /// chunk file over HTTPS without holding S3 credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presigned_url: Option<String>,
 }
 
 struct Normalizer {
     channel: Channel,
+    store: Box<dyn ObjectStore>,
+    // Kept alongside `store` purely to sign presigned URLs; the generic
+    // `ObjectStore` trait doesn't (and shouldn't) expose S3-specific
+    // presigning to the other backends.
     s3_client: aws_sdk_s3::Client,
     bucket_name: String,
+    presign_urls: bool,
+    presign_expiry: Duration,
     hyphen_regex: Regex,
     whitespace_regex: Regex,
 }
@@ -84,16 +103,47 @@ impl Normalizer {
             .await?;
 
         // Configure S3 client
-        let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        let credentials = build_credentials_chain().await;
+
+        let s3_config = aws_sdk_s3::config::Builder::new()
             .endpoint_url(s3_endpoint)
-            .load()
-            .await;
-        let s3_client = aws_sdk_s3::Client::new(&config);
+            .credentials_provider(credentials)
+            .region(aws_sdk_s3::config::Region::new("us-east-1"))
+            .force_path_style(true)
+            .build();
+
+        let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
+        let store_backend = store::backend_name();
+        let store = store::build_store(s3_client.clone(), bucket_name.clone()).await?;
+
+        let presign_urls = std::env::var("PRESIGN_URLS")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        // `presign_get` only knows how to sign against `s3_client`, so it
+        // isn't meaningful for the Azure/GCS/local-filesystem backends the
+        // `ObjectStore` abstraction also supports: a URL signed against S3
+        // would point at an endpoint nothing was ever written to, and
+        // downstream consumers would fail silently against it. Fail fast at
+        // startup instead.
+        if presign_urls && store_backend != "s3" {
+            anyhow::bail!(
+                "PRESIGN_URLS=true requires STORE_BACKEND=s3, but it is set to {}",
+                store_backend
+            );
+        }
+        let presign_expiry = std::env::var("PRESIGN_EXPIRY_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_PRESIGN_EXPIRY);
 
         Ok(Self {
             channel,
+            store,
             s3_client,
             bucket_name,
+            presign_urls,
+            presign_expiry,
             hyphen_regex: Regex::new(r"-\s*\n\s*").unwrap(),
             whitespace_regex: Regex::new(r"\s+").unwrap(),
         })
@@ -163,15 +213,23 @@ impl Normalizer {
     }
 
     async fn upload_to_s3(&self, key: &str, data: &[u8]) -> Result<String> {
-        self.s3_client
-            .put_object()
+        self.store.put(key, data.to_vec()).await?;
+        Ok(format!("s3://{}/{}", self.bucket_name, key))
+    }
+
+    /// Generates a presigned GET URL for `key`, signed with SigV4 and valid
+    /// for `self.presign_expiry`.
+    async fn presign_get(&self, key: &str) -> Result<String> {
+        let presigned = self
+            .s3_client
+            .get_object()
             .bucket(&self.bucket_name)
             .key(key)
-            .body(data.to_vec().into())
-            .send()
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(
+                self.presign_expiry,
+            )?)
             .await?;
-
-        Ok(format!("s3://{}/{}", self.bucket_name, key))
+        Ok(presigned.uri().to_string())
     }
 
     async fn process_message(&self, msg: PageExtractedMessage) -> Result<()> {
@@ -186,12 +244,25 @@ impl Normalizer {
         let chunk_key = format!("chunks/{}/{}.json", msg.doc_id, msg.page_no);
         let chunk_uri = self.upload_to_s3(&chunk_key, &json_data).await?;
 
+        let presigned_url = if self.presign_urls {
+            match self.presign_get(&chunk_key).await {
+                Ok(url) => Some(url),
+                Err(e) => {
+                    error!("Failed to presign {}: {}", chunk_key, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         // Publish chunked message
         let chunked_msg = PageChunkedMessage {
             doc_id: msg.doc_id,
             page_no: msg.page_no,
             chunks,
             chunk_uri,
+            presigned_url,
         };
 
         let payload = serde_json::to_vec(&chunked_msg)?;